@@ -0,0 +1,38 @@
+//! Transform cost over representative sizes, run with `cargo bench --features bench`
+//!
+//! Compares a single planned FFT reused across iterations against `proc::spectrum`, which
+//! allocates its buffer and looks up the plan on every call.
+
+use std::time::{Duration, Instant};
+
+use rustfft::FftPlanner;
+use wavedata::proc::{benchmark_fft, spectrum};
+use wavedata::sampling::Samples;
+
+const ITERS: usize = 1000;
+
+fn per_iter(elapsed: Duration) -> Duration {
+    elapsed / (ITERS as u32)
+}
+
+fn main() {
+    println!("{:>8} {:>14} {:>14}", "len", "planned", "spectrum");
+    for len in [256, 1024, 4096, 16384] {
+        let planned = benchmark_fft(len, ITERS);
+
+        let mut fft = FftPlanner::new();
+        let samples: Vec<f32> = (0..len).map(|i| (i % 7) as f32).collect();
+        let start = Instant::now();
+        for _ in 0..ITERS {
+            std::hint::black_box(spectrum(Samples(&samples), &mut fft));
+        }
+        let spectrum = start.elapsed();
+
+        println!(
+            "{:>8} {:>14?} {:>14?}",
+            len,
+            per_iter(planned),
+            per_iter(spectrum)
+        );
+    }
+}
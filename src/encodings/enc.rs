@@ -1,4 +1,6 @@
 pub mod nrz {
+    use crate::units::Frequency;
+
     #[derive(Debug, PartialEq)]
     pub enum Value {
         StartOfFrame,
@@ -16,6 +18,7 @@ pub mod nrz {
         Complete,
     }
 
+    #[derive(Clone)]
     pub struct Parameters {
         payload: Vec<u8>, // Bytes
         stuff_bit_after: u8,
@@ -24,13 +27,47 @@ pub mod nrz {
     impl Parameters {
         pub fn new(payload: Vec<u8>, stuff_bit_after: u8) -> Self {
             Self {
-                payload: payload,
-                stuff_bit_after: stuff_bit_after,
+                payload,
+                stuff_bit_after,
+            }
+        }
+
+        /// Payload bits per second left of the baudrate after the start of frame, the stuff bits
+        /// and the end of frame are transmitted, zero for an empty payload
+        pub fn effective_rate(&self, baudrate: Frequency) -> Frequency {
+            if self.payload.is_empty() {
+                return Frequency::new(0.0);
+            }
+            let symbols = NRZ::new(self.clone()).count();
+            let payload_bits = self.payload.len() * 8;
+            Frequency::new(baudrate.value() * (payload_bits as f32) / (symbols as f32))
+        }
+
+        /// Number of symbols a complete frame consists of, counted without running the encoder:
+        /// the start of frame, the payload bits, the stuff bits and the end of frame
+        pub fn expected_transition_count(&self) -> usize {
+            let mut zeros = 0u8;
+            let mut stuff_bits = 0;
+            for byte in self.payload.iter() {
+                for offset in 0..8 {
+                    if zeros >= self.stuff_bit_after {
+                        stuff_bits += 1;
+                        zeros = 0;
+                    }
+                    if byte & (0b1_u8 << (7 - offset)) != 0 {
+                        zeros = 0;
+                    } else {
+                        zeros += 1;
+                    }
+                }
             }
+            1 + self.payload.len() * 8 + stuff_bits + (self.stuff_bit_after as usize) + 2
         }
     }
 
-    struct State {
+    /// Progress of an encoder through its frame, to resume encoding from
+    #[derive(Clone)]
+    pub struct State {
         payload_offset: usize,
         current_bit_offset: u8,
         contigous_zeros: u8,
@@ -56,11 +93,20 @@ pub mod nrz {
     impl NRZ {
         pub fn new(c: Parameters) -> Self {
             Self {
-                c: c,
+                c,
                 m: State::init(),
             }
         }
 
+        pub fn save_state(&self) -> State {
+            self.m.clone()
+        }
+
+        /// Continues from a state saved from an encoder of the same parameters
+        pub fn restore_state(&mut self, state: State) {
+            self.m = state;
+        }
+
         pub fn current(&self) -> Value {
             match self.m.sm {
                 StateMachine::Start => Value::StartOfFrame,
@@ -77,7 +123,7 @@ pub mod nrz {
         }
 
         pub fn advance(&mut self) {
-            let sm = self.m.sm.clone();
+            let sm = self.m.sm;
             self.m.sm = match sm {
                 StateMachine::Start => StateMachine::Payload,
                 StateMachine::Payload => {
@@ -337,5 +383,65 @@ pub mod nrz {
                 ]
             );
         }
+
+        #[test]
+        fn effective_rate_drops_with_stuffing() {
+            let baudrate = Frequency::new(1000.0);
+            let zeros = Parameters::new(vec![0b_0000_0000, 0b_0000_0000], 4);
+            let balanced = Parameters::new(vec![0b_0101_0101, 0b_0101_0101], 4);
+
+            // 16 payload bits in 1 + 16 + 3 stuff bits + 6 end of frame symbols
+            assert_eq!(
+                zeros.effective_rate(baudrate),
+                Frequency::new(16000.0 / 26.0)
+            );
+            // 16 payload bits in 1 + 16 + 6 end of frame symbols
+            assert_eq!(
+                balanced.effective_rate(baudrate),
+                Frequency::new(16000.0 / 23.0)
+            );
+        }
+
+        #[test]
+        fn effective_rate_of_empty_payload() {
+            let c = Parameters::new(vec![], 4);
+            assert_eq!(
+                c.effective_rate(Frequency::new(1000.0)),
+                Frequency::new(0.0)
+            );
+        }
+
+        #[test]
+        fn expected_transition_count_matches_encoder() {
+            let payloads = [
+                vec![0b_0000_0000],
+                vec![0b_1111_1111],
+                vec![0b_1000_0100, 0b_0000_0001],
+                vec![0b_1001_1000, 0b_0010_0010, 0b_0000_0000],
+            ];
+            for payload in payloads.iter() {
+                for stuff_bit_after in [3, 4, 5, 9] {
+                    let c = Parameters::new(payload.clone(), stuff_bit_after);
+                    assert_eq!(c.expected_transition_count(), NRZ::new(c).count());
+                }
+            }
+        }
+
+        #[test]
+        fn resumed_encoding_matches_single_pass() {
+            let c = Parameters::new(vec![0b_1000_0100, 0b_0000_0001, 0b_1001_1000], 4);
+            let single_pass: Vec<Value> = NRZ::new(c.clone()).collect();
+
+            for split in [0, 1, 9, 17, single_pass.len()] {
+                let mut first = NRZ::new(c.clone());
+                let mut resumed: Vec<Value> = first.by_ref().take(split).collect();
+                let state = first.save_state();
+
+                let mut second = NRZ::new(c.clone());
+                second.restore_state(state);
+                resumed.extend(second);
+                assert_eq!(resumed, single_pass);
+            }
+        }
     }
 }
@@ -1 +1,48 @@
 pub mod enc;
+
+/// Deterministic pseudo-random payload of `len` bytes, the same for the same seed
+///
+/// The bytes are drawn from a SplitMix64 generator.
+pub fn test_payload(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            (z ^ (z >> 31)) as u8
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use enc::nrz::{Parameters, Value, NRZ};
+
+    #[test]
+    fn test_payload_is_reproducible() {
+        assert_eq!(test_payload(64, 42), test_payload(64, 42));
+        assert_ne!(test_payload(64, 42), test_payload(64, 43));
+        assert_eq!(test_payload(10, 7), test_payload(64, 7)[..10]);
+    }
+
+    #[test]
+    fn test_payload_bits_come_out_of_encoder() {
+        for seed in 0..8 {
+            let payload = test_payload(16, seed);
+            let bits: Vec<bool> = NRZ::new(Parameters::new(payload.clone(), 4))
+                .filter_map(|value| match value {
+                    Value::Bit(bit) => Some(bit),
+                    _ => None,
+                })
+                .collect();
+            let expected: Vec<bool> = payload
+                .iter()
+                .flat_map(|byte| (0..8).map(move |offset| byte & (0b1_u8 << (7 - offset)) != 0))
+                .collect();
+            assert_eq!(bits, expected);
+        }
+    }
+}
@@ -1,7 +1,10 @@
 pub use rustfft as rfft;
 
 pub mod encodings;
+pub mod proc;
 pub mod sampling;
 pub mod signals;
+#[cfg(test)]
+mod testing;
 pub mod units;
 pub mod waves;
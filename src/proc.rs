@@ -0,0 +1,571 @@
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+
+use crate::sampling::{SampleCount, Samples, SamplesMut, SamplingRate};
+use crate::units::{Frequency, Proportion};
+
+/// Unnormalized complex spectrum of the real samples
+pub fn spectrum(s: Samples, fft: &mut FftPlanner<f32>) -> Vec<Complex<f32>> {
+    let mut buffer: Vec<Complex<f32>> =
+        s.as_slice().iter().map(|x| Complex::new(*x, 0.0)).collect();
+    fft.plan_fft_forward(buffer.len()).process(&mut buffer);
+    buffer
+}
+
+/// Frequency of the strongest bin between DC (exclusive) and Nyquist
+pub fn peak_frequency(s: Samples, rate: SamplingRate, fft: &mut FftPlanner<f32>) -> Frequency {
+    let len = s.as_slice().len();
+    let bins = spectrum(s, fft);
+    let peak = bins
+        .iter()
+        .enumerate()
+        .take(len / 2 + 1)
+        .skip(1)
+        .max_by(|a, b| a.1.norm_sqr().total_cmp(&b.1.norm_sqr()))
+        .map(|(idx, _)| idx)
+        .unwrap_or(0);
+    bin_frequency(peak, len, rate)
+}
+
+/// Time taken by `iters` forward transforms of length `len` through a single planned FFT
+///
+/// Gives a quick impression of the transform cost on the current hardware.
+pub fn benchmark_fft(len: usize, iters: usize) -> std::time::Duration {
+    benchmark_fft_output(len, iters).0
+}
+
+/// [`benchmark_fft`] along with the output of the last transform
+///
+/// Each iteration transforms a unit impulse, so the returned spectrum should be flat; it is all
+/// zero if no iteration ran.
+pub(crate) fn benchmark_fft_output(
+    len: usize,
+    iters: usize,
+) -> (std::time::Duration, Vec<Complex<f32>>) {
+    let fft = FftPlanner::<f32>::new().plan_fft_forward(len);
+    let mut buffer = vec![Complex::new(0.0, 0.0); len];
+    let mut scratch = vec![Complex::new(0.0, 0.0); fft.get_inplace_scratch_len()];
+
+    let start = std::time::Instant::now();
+    for _ in 0..iters {
+        buffer.iter_mut().for_each(|x| *x = Complex::new(0.0, 0.0));
+        if let Some(first) = buffer.first_mut() {
+            *first = Complex::new(1.0, 0.0);
+        }
+        fft.process_with_scratch(&mut buffer, &mut scratch);
+    }
+    (start.elapsed(), buffer)
+}
+
+/// Shortest power of two transform length whose bins are at most `resolution` apart
+pub fn fft_len_for_resolution(resolution: Frequency, rate: SamplingRate) -> usize {
+    (((rate.value() as f32) / resolution.value()).ceil().max(1.0) as usize).next_power_of_two()
+}
+
+/// Spacing of the bins of a transform of length `len`
+pub fn resolution_for_len(len: usize, rate: SamplingRate) -> Frequency {
+    bin_frequency(1, len, rate)
+}
+
+/// Width of the band holding `fraction` of the capture's energy, leaving equal shares of the
+/// remainder below and above it
+pub fn occupied_bandwidth(
+    s: Samples,
+    rate: SamplingRate,
+    fraction: Proportion,
+    fft: &mut FftPlanner<f32>,
+) -> Frequency {
+    let len = s.as_slice().len();
+    let energies: Vec<f32> = spectrum(s, fft)
+        .iter()
+        .take(len / 2 + 1)
+        .map(|x| x.norm_sqr())
+        .collect();
+    let total: f32 = energies.iter().sum();
+    let tail = total * (1.0 - fraction.value()) / 2.0;
+
+    let mut cumulative = 0.0f32;
+    let mut edges = (None, None);
+    for (bin, energy) in energies.iter().enumerate() {
+        cumulative += energy;
+        if edges.0.is_none() && cumulative > tail {
+            edges.0 = Some(bin);
+        }
+        if edges.1.is_none() && cumulative >= total - tail {
+            edges.1 = Some(bin);
+        }
+    }
+    match edges {
+        (Some(lower), Some(upper)) => Frequency::new(
+            bin_frequency(upper, len, rate).value() - bin_frequency(lower, len, rate).value(),
+        ),
+        _ => Frequency::new(0.0),
+    }
+}
+
+/// Total harmonic distortion as the energy of the harmonics relative to the energy of the
+/// fundamental
+///
+/// The capture is Hann windowed, and the energy of each component is summed over the bins around
+/// it to collect the window's main lobe. Harmonics are considered up to Nyquist. Zero if there is
+/// no energy at the fundamental.
+pub fn thd(
+    s: Samples,
+    fundamental: Frequency,
+    rate: SamplingRate,
+    fft: &mut FftPlanner<f32>,
+) -> Proportion {
+    let bins = windowed_spectrum(s, fft);
+    let len = bins.len();
+    let energy_at = |f: f32| {
+        let center = (f * (len as f32) / (rate.value() as f32)).round() as usize;
+        band_energy(&bins, center.saturating_sub(3)..=center + 3)
+    };
+
+    let nyquist = (rate.value() as f32) / 2.0;
+    let harmonics: f32 = (2..)
+        .map(|n| (n as f32) * fundamental.value())
+        .take_while(|f| *f < nyquist)
+        .map(energy_at)
+        .sum();
+    let fundamental_energy = energy_at(fundamental.value());
+    if fundamental_energy == 0.0 {
+        return Proportion::new(0.0);
+    }
+    Proportion::new(harmonics / fundamental_energy)
+}
+
+/// Fraction of the capture's energy falling outside the expected band, as spread by nonlinear
+/// distortion such as clipping
+pub fn spectral_regrowth(
+    s: Samples,
+    band: (Frequency, Frequency),
+    rate: SamplingRate,
+    fft: &mut FftPlanner<f32>,
+) -> Proportion {
+    let bins = windowed_spectrum(s, fft);
+    let len = bins.len();
+    let to_bin = |f: Frequency| (f.value() * (len as f32) / (rate.value() as f32)).round() as usize;
+
+    let total = band_energy(&bins, 0..=len / 2);
+    if total == 0.0 {
+        return Proportion::new(0.0);
+    }
+    let in_band = band_energy(&bins, to_bin(band.0)..=to_bin(band.1));
+    Proportion::new((total - in_band) / total)
+}
+
+/// One-sided power spectral density averaged over overlapping windowed segments (Welch's method)
+///
+/// Each segment of the capture is multiplied by the window, which must be as long as the
+/// segments, and the resulting periodograms are averaged. The density is in power per Hz up to
+/// Nyquist; a capture shorter than a segment yields no bins.
+pub fn welch_psd(
+    s: Samples,
+    rate: SamplingRate,
+    segment: SampleCount,
+    overlap: Proportion,
+    window: &[f32],
+    fft: &mut FftPlanner<f32>,
+) -> Result<Vec<(Frequency, f32)>, crate::sampling::Error> {
+    let len = segment.value();
+    if len == 0 || window.len() != len {
+        return Err(crate::sampling::Error::LengthMismatch);
+    }
+    let hop = (((len as f32) * (1.0 - overlap.value())).round() as usize).max(1);
+    let window_power: f32 = window.iter().map(|w| w * w).sum();
+
+    let mut psd = vec![0.0f32; len / 2 + 1];
+    let mut segments = 0;
+    let mut buffer = vec![0.0f32; len];
+    for start in (0..s.as_slice().len().saturating_sub(len - 1)).step_by(hop) {
+        buffer.copy_from_slice(&s.as_slice()[start..start + len]);
+        crate::sampling::apply_window(SamplesMut(&mut buffer), window)?;
+        let bins = spectrum(Samples(&buffer), fft);
+        psd.iter_mut()
+            .zip(bins.iter())
+            .for_each(|(p, bin)| *p += bin.norm_sqr());
+        segments += 1;
+    }
+    if segments == 0 {
+        return Ok(Vec::new());
+    }
+
+    let scale = 1.0 / ((segments as f32) * (rate.value() as f32) * window_power);
+    Ok(psd
+        .into_iter()
+        .enumerate()
+        .map(|(bin, p)| {
+            // Negative frequencies are folded onto the positive ones
+            let one_sided = if bin == 0 || 2 * bin == len { 1.0 } else { 2.0 };
+            (bin_frequency(bin, len, rate), p * scale * one_sided)
+        })
+        .collect())
+}
+
+/// Magnitude-squared coherence of the two captures per frequency up to Nyquist
+///
+/// Cross and auto spectra are averaged over Hann windowed segments overlapping by half, like the
+/// periodograms of [`welch_psd`]. Coherence near one means `b` is a linearly filtered copy of `a` at that
+/// frequency; bins without energy report zero. The longer capture is cut to the shorter one, and
+/// an empty segment yields no bins.
+pub fn coherence(
+    a: Samples,
+    b: Samples,
+    rate: SamplingRate,
+    segment: SampleCount,
+    fft: &mut FftPlanner<f32>,
+) -> Vec<(Frequency, Proportion)> {
+    let len = segment.value();
+    if len == 0 {
+        return Vec::new();
+    }
+    let hop = SampleCount::from((len / 2).max(1));
+    let mut cross = vec![Complex::new(0.0f32, 0.0); len / 2 + 1];
+    let mut auto_a = vec![0.0f32; len / 2 + 1];
+    let mut auto_b = vec![0.0f32; len / 2 + 1];
+    for (segment_a, segment_b) in a.windows_hop(segment, hop).zip(b.windows_hop(segment, hop)) {
+        let bins_a = windowed_spectrum(segment_a, fft);
+        let bins_b = windowed_spectrum(segment_b, fft);
+        for (bin, (x, y)) in bins_a
+            .iter()
+            .zip(bins_b.iter())
+            .take(len / 2 + 1)
+            .enumerate()
+        {
+            cross[bin] += x * y.conj();
+            auto_a[bin] += x.norm_sqr();
+            auto_b[bin] += y.norm_sqr();
+        }
+    }
+
+    cross
+        .iter()
+        .zip(auto_a.iter().zip(auto_b.iter()))
+        .enumerate()
+        .map(|(bin, (c, (pa, pb)))| {
+            let power = pa * pb;
+            let msc = if power > 0.0 {
+                c.norm_sqr() / power
+            } else {
+                0.0
+            };
+            (bin_frequency(bin, len, rate), Proportion::new(msc))
+        })
+        .collect()
+}
+
+/// Spectrum of the Hann windowed samples
+pub(crate) fn windowed_spectrum(s: Samples, fft: &mut FftPlanner<f32>) -> Vec<Complex<f32>> {
+    let mut windowed = s.as_slice().to_vec();
+    let mut window = vec![0.0; windowed.len()];
+    crate::sampling::fill_hann(SamplesMut(&mut window));
+    windowed
+        .iter_mut()
+        .zip(window.iter())
+        .for_each(|(x, w)| *x *= w);
+    spectrum(Samples(&windowed), fft)
+}
+
+/// Summed energy of the positive frequency bins in the range
+fn band_energy(bins: &[Complex<f32>], range: std::ops::RangeInclusive<usize>) -> f32 {
+    let nyquist_bin = bins.len() / 2;
+    range
+        .filter(|bin| *bin <= nyquist_bin)
+        .filter_map(|bin| bins.get(bin))
+        .map(|bin| bin.norm_sqr())
+        .sum()
+}
+
+fn bin_frequency(bin: usize, len: usize, rate: SamplingRate) -> Frequency {
+    Frequency::new((bin * rate.value()) as f32 / (len as f32))
+}
+
+/// Recursive sliding DFT over the last `bins` pushed samples
+///
+/// Every [`SlidingDft::push`] updates all bins in O(bins) instead of recomputing a full transform,
+/// which makes it suitable for continuously monitoring a band. The recursion accumulates rounding
+/// error indefinitely, as the twiddle factors sit exactly on the unit circle and errors are never
+/// damped out. For long running streams call [`SlidingDft::reset`] periodically or cross-check
+/// against a full transform.
+pub struct SlidingDft {
+    rate: SamplingRate,
+    history: Vec<f32>,
+    oldest: usize,
+    twiddles: Vec<Complex<f32>>,
+    bins: Vec<Complex<f32>>,
+}
+
+impl SlidingDft {
+    pub fn new(bins: usize, rate: SamplingRate) -> Self {
+        let twiddles = (0..bins)
+            .map(|k| {
+                let angle = 2.0f32 * std::f32::consts::PI * (k as f32) / (bins as f32);
+                Complex::from_polar(1.0, angle)
+            })
+            .collect();
+        Self {
+            rate,
+            history: vec![0.0; bins],
+            oldest: 0,
+            twiddles,
+            bins: vec![Complex::new(0.0, 0.0); bins],
+        }
+    }
+
+    /// Shifts `sample` into the window, dropping the oldest one
+    ///
+    /// A transform with zero bins has no window and ignores every sample.
+    pub fn push(&mut self, sample: f32) {
+        if self.history.is_empty() {
+            return;
+        }
+        let delta = sample - self.history[self.oldest];
+        self.history[self.oldest] = sample;
+        self.oldest = (self.oldest + 1) % self.history.len();
+
+        for (bin, twiddle) in self.bins.iter_mut().zip(self.twiddles.iter()) {
+            *bin = (*bin + delta) * twiddle;
+        }
+    }
+
+    /// Clears the window and the accumulated bins
+    pub fn reset(&mut self) {
+        self.history.iter_mut().for_each(|s| *s = 0.0);
+        self.bins
+            .iter_mut()
+            .for_each(|b| *b = Complex::new(0.0, 0.0));
+        self.oldest = 0;
+    }
+
+    /// Current DFT of the window, oldest sample first
+    pub fn bins(&self) -> &[Complex<f32>] {
+        &self.bins
+    }
+
+    /// Center frequency of the given bin
+    pub fn frequency(&self, bin: usize) -> Frequency {
+        bin_frequency(bin, self.bins.len(), self.rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{noise, tone};
+
+    #[test]
+    fn sliding_dft_matches_full_fft() {
+        let len = 64;
+        let input = noise(3 * len, 1);
+
+        let mut sdft = SlidingDft::new(len, SamplingRate::new(1000));
+        input.iter().for_each(|s| sdft.push(*s));
+
+        let mut expected: Vec<Complex<f32>> = input[input.len() - len..]
+            .iter()
+            .map(|s| Complex::new(*s, 0.0))
+            .collect();
+        rustfft::FftPlanner::new()
+            .plan_fft_forward(len)
+            .process(&mut expected);
+
+        for (actual, expected) in sdft.bins().iter().zip(expected.iter()) {
+            assert!((actual - expected).norm() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn sliding_dft_without_bins() {
+        let mut sdft = SlidingDft::new(0, SamplingRate::new(1000));
+        sdft.push(1.0);
+        sdft.reset();
+        assert!(sdft.bins().is_empty());
+    }
+
+    #[test]
+    fn sliding_dft_bin_frequency() {
+        let sdft = SlidingDft::new(100, SamplingRate::new(1000));
+        assert_eq!(sdft.frequency(25), Frequency::new(250.0));
+    }
+
+    #[test]
+    fn thd_of_pure_and_clipped_sine() {
+        let rate = SamplingRate::new(48000);
+        let fundamental = Frequency::new(1000.0);
+        let mut fft = FftPlanner::new();
+
+        let pure = tone(1000.0, 48000, 4800);
+        assert!(thd(Samples(&pure), fundamental, rate, &mut fft).value() < 1e-4);
+
+        let clipped: Vec<f32> = pure.iter().map(|x| x.clamp(-0.5, 0.5)).collect();
+        assert!(thd(Samples(&clipped), fundamental, rate, &mut fft).value() > 0.01);
+    }
+
+    #[test]
+    fn spectral_regrowth_of_clean_and_clipped_carrier() {
+        let rate = SamplingRate::new(48000);
+        let band = (Frequency::new(900.0), Frequency::new(1100.0));
+        let mut fft = FftPlanner::new();
+
+        let clean = tone(1000.0, 48000, 4800);
+        let clipped: Vec<f32> = clean.iter().map(|x| x.clamp(-0.3, 0.3)).collect();
+
+        let clean_regrowth = spectral_regrowth(Samples(&clean), band, rate, &mut fft);
+        let clipped_regrowth = spectral_regrowth(Samples(&clipped), band, rate, &mut fft);
+        assert!(clean_regrowth.value() < 1e-4);
+        assert!(clipped_regrowth.value() > 0.05);
+    }
+
+    #[test]
+    fn benchmark_fft_takes_time() {
+        assert!(benchmark_fft(1024, 16) > std::time::Duration::ZERO);
+
+        let (elapsed, spectrum) = benchmark_fft_output(1024, 16);
+        assert!(elapsed > std::time::Duration::ZERO);
+        assert_eq!(spectrum.len(), 1024);
+        assert!(spectrum
+            .iter()
+            .all(|x| (x - Complex::new(1.0, 0.0)).norm() < 1e-4));
+
+        let (_, untouched) = benchmark_fft_output(8, 0);
+        assert!(untouched.iter().all(|x| *x == Complex::new(0.0, 0.0)));
+    }
+
+    #[test]
+    fn fft_len_achieves_resolution() {
+        let rate = SamplingRate::new(48000);
+        for resolution in [0.5, 1.0, 3.0, 46.875, 100.0, 24000.0] {
+            let len = fft_len_for_resolution(Frequency::new(resolution), rate);
+            assert!(len.is_power_of_two());
+            assert!(resolution_for_len(len, rate).value() <= resolution);
+            assert!(resolution_for_len(len / 2, rate).value() > resolution || len == 1);
+        }
+        assert_eq!(fft_len_for_resolution(Frequency::new(46.875), rate), 1024);
+    }
+
+    #[test]
+    fn welch_psd_of_white_noise_is_flatter() {
+        let rate = SamplingRate::new(8000);
+        let noise = noise(8192, 11);
+        let spread = |psd: &[(Frequency, f32)]| {
+            let values: Vec<f32> = psd[1..psd.len() - 1].iter().map(|p| p.1).collect();
+            let mean = values.iter().sum::<f32>() / (values.len() as f32);
+            let variance =
+                values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / (values.len() as f32);
+            (mean, variance.sqrt() / mean)
+        };
+        let mut fft = FftPlanner::new();
+        let mut window = vec![0.0; 256];
+        crate::sampling::fill_hann(SamplesMut(&mut window));
+        let welch = welch_psd(
+            Samples(&noise),
+            rate,
+            SampleCount::from(256),
+            Proportion::new(0.5),
+            &window,
+            &mut fft,
+        )
+        .unwrap();
+        let single = welch_psd(
+            Samples(&noise[..256]),
+            rate,
+            SampleCount::from(256),
+            Proportion::new(0.5),
+            &window,
+            &mut fft,
+        )
+        .unwrap();
+
+        let (welch_mean, welch_spread) = spread(&welch);
+        let (_, single_spread) = spread(&single);
+        assert!(welch_spread < 0.3 * single_spread);
+        // Uniform noise on [-1, 1] has a variance of 1/3 spread over 4 kHz
+        assert!((welch_mean - 1.0 / 3.0 / 4000.0).abs() < 0.1 / 3.0 / 4000.0);
+        assert!(welch_psd(
+            Samples(&noise),
+            rate,
+            SampleCount::from(128),
+            Proportion::new(0.5),
+            &window,
+            &mut fft
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn delayed_copy_is_coherent() {
+        let rate = SamplingRate::new(8000);
+        let reference = noise(8192, 5);
+        let delayed: Vec<f32> = noise(8192, 6)
+            .iter()
+            .enumerate()
+            .map(|(i, n)| 0.5 * reference[i.saturating_sub(5)] + 0.05 * n)
+            .collect();
+        let unrelated = noise(8192, 7);
+        let mut fft = FftPlanner::new();
+        let mean = |msc: Vec<(Frequency, Proportion)>| {
+            msc.iter().map(|m| m.1.value()).sum::<f32>() / (msc.len() as f32)
+        };
+
+        let related = coherence(
+            Samples(&reference),
+            Samples(&delayed),
+            rate,
+            SampleCount::from(256),
+            &mut fft,
+        );
+        assert!(related.iter().skip(1).all(|m| m.1.value() > 0.8));
+        assert!(mean(related) > 0.9);
+        let unrelated = coherence(
+            Samples(&reference),
+            Samples(&unrelated),
+            rate,
+            SampleCount::from(256),
+            &mut fft,
+        );
+        assert!(mean(unrelated) < 0.1);
+    }
+
+    #[test]
+    fn thd_of_empty_capture() {
+        let mut fft = FftPlanner::new();
+        let empty = Samples(&[]);
+        assert!(windowed_spectrum(empty, &mut fft).is_empty());
+        assert_eq!(
+            thd(
+                empty,
+                Frequency::new(1000.0),
+                SamplingRate::new(8000),
+                &mut fft
+            ),
+            Proportion::new(0.0)
+        );
+    }
+
+    #[test]
+    fn regrowth_of_empty_capture() {
+        assert_eq!(
+            spectral_regrowth(
+                Samples(&[]),
+                (Frequency::new(900.0), Frequency::new(1100.0)),
+                SamplingRate::new(8000),
+                &mut FftPlanner::new()
+            ),
+            Proportion::new(0.0)
+        );
+    }
+
+    #[test]
+    fn coherence_of_empty_segment() {
+        let capture = [0.5f32; 16];
+        assert!(coherence(
+            Samples(&capture),
+            Samples(&capture),
+            SamplingRate::new(8000),
+            SampleCount::from(0),
+            &mut FftPlanner::new()
+        )
+        .is_empty());
+    }
+}
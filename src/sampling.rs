@@ -1,8 +1,56 @@
-use crate::units::{Amplitude, Time};
+use crate::signals::filters::{Filter, MovingAverage};
+use crate::signals::{Signal, WaveSignal};
+use crate::units::{Amplitude, Frequency, Proportion, Time};
+use crate::waves::{Sine, Wave};
 
+#[derive(Debug)]
+pub enum Error {
+    LengthMismatch,
+}
+
+#[derive(Clone, Copy)]
 pub struct Samples<'a>(pub &'a [f32]);
 pub struct SamplesMut<'a>(pub &'a mut [f32]);
 
+impl<'a> Samples<'a> {
+    pub fn as_slice(&self) -> &'a [f32] {
+        self.0
+    }
+
+    /// Whole windows of `window` samples, each starting `hop` samples after the previous one
+    pub fn windows_hop(
+        &self,
+        window: SampleCount,
+        hop: SampleCount,
+    ) -> impl Iterator<Item = Samples<'a>> {
+        let samples = self.0;
+        (0..(samples.len() + 1).saturating_sub(window.value()))
+            .step_by(hop.value().max(1))
+            .map(move |start| Samples(&samples[start..start + window.value()]))
+    }
+}
+
+impl<'a> From<&'a [f32]> for Samples<'a> {
+    fn from(value: &'a [f32]) -> Self {
+        Self(value)
+    }
+}
+
+impl<'a> SamplesMut<'a> {
+    pub fn as_slice(&self) -> &[f32] {
+        self.0
+    }
+    pub fn as_mut_slice(&mut self) -> &mut [f32] {
+        self.0
+    }
+}
+
+impl<'a> From<&'a mut [f32]> for SamplesMut<'a> {
+    fn from(value: &'a mut [f32]) -> Self {
+        Self(value)
+    }
+}
+
 /// Number of samplings per second
 #[derive(Clone, Copy)]
 pub struct SamplingRate(usize);
@@ -13,10 +61,29 @@ impl SamplingRate {
     pub fn value(self) -> usize {
         self.0
     }
+    /// Normalized digital frequency `f/fs` of the given frequency
+    pub fn normalized(self, f: Frequency) -> Proportion {
+        Proportion::new(f.value() / (self.0 as f32))
+    }
+    /// Angular digital frequency `2πf/fs` in radians per sample
+    pub fn radians_per_sample(self, f: Frequency) -> f32 {
+        2.0f32 * std::f32::consts::PI * self.normalized(f).value()
+    }
+    /// Nyquist frequency, the highest frequency representable without aliasing
+    pub fn max_frequency(self) -> Frequency {
+        Frequency::new((self.0 as f32) / 2.0)
+    }
 }
 /// Number of samples taken
+#[derive(Clone, Copy)]
 pub struct SampleCount(usize);
 
+impl SampleCount {
+    pub fn value(self) -> usize {
+        self.0
+    }
+}
+
 impl From<usize> for SampleCount {
     fn from(value: usize) -> Self {
         Self(value)
@@ -24,40 +91,368 @@ impl From<usize> for SampleCount {
 }
 
 impl SamplingRate {
-    fn sample(&self, amount: SampleCount) -> Time {
-        let rate = self.0 as f32;
-        let amount = amount.0 as f32;
-        Time::new(amount / rate)
-    }
-
     fn increment(&self) -> Time {
         Time::new(1.0f32 / (self.0 as f32))
     }
 }
 
+/// Checks that the highest frequency to be generated does not alias at the rate, returning it if
+/// it would
+///
+/// A tone exactly at Nyquist is rejected too: its sampled amplitude depends on its phase, and it
+/// can sample to all zeros.
+pub fn check_nyquist(max_frequency: Frequency, rate: SamplingRate) -> Result<(), Frequency> {
+    if max_frequency >= rate.max_frequency() {
+        Err(max_frequency)
+    } else {
+        Ok(())
+    }
+}
+
+/// Writes the samples as little-endian 32 bit floats
+pub fn write_raw_f32<W: std::io::Write>(w: &mut W, s: Samples) -> std::io::Result<()> {
+    for sample in s.as_slice().iter() {
+        w.write_all(&sample.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Writes the samples as little-endian full scale 16 bit PCM
+pub fn write_raw_i16<W: std::io::Write>(w: &mut W, s: Samples) -> std::io::Result<()> {
+    for sample in s.as_slice().iter() {
+        w.write_all(&Amplitude::new(*sample).to_i16().to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Multiplies the samples element-wise with the window
+pub fn apply_window(mut s: SamplesMut, window: &[f32]) -> Result<(), Error> {
+    if s.as_slice().len() != window.len() {
+        return Err(Error::LengthMismatch);
+    }
+    s.as_mut_slice()
+        .iter_mut()
+        .zip(window.iter())
+        .for_each(|(sample, w)| *sample *= w);
+    Ok(())
+}
+
+/// Fills the buffer with a symmetric Hann window
+pub fn fill_hann(s: SamplesMut) {
+    fill_raised_cosine(s, 0.5, 0.5)
+}
+
+/// Fills the buffer with a symmetric Hamming window
+pub fn fill_hamming(s: SamplesMut) {
+    fill_raised_cosine(s, 0.54, 0.46)
+}
+
+fn fill_raised_cosine(mut s: SamplesMut, a0: f32, a1: f32) {
+    let len = s.as_slice().len();
+    if len == 0 {
+        return;
+    }
+    if len == 1 {
+        s.as_mut_slice()[0] = 1.0;
+        return;
+    }
+    let span = (len - 1) as f32;
+    for (idx, w) in s.as_mut_slice().iter_mut().enumerate() {
+        *w = a0 - a1 * (2.0f32 * std::f32::consts::PI * (idx as f32) / span).cos();
+    }
+}
+
+/// Ramps the start up from and the end down to silence in place along a raised cosine
+///
+/// The first and last samples become zero and the ramps reach full amplitude `fade_in` samples
+/// from the start and `fade_out` samples from the end.
+pub fn fade(mut s: SamplesMut, fade_in: SampleCount, fade_out: SampleCount) {
+    let ramp = |idx: usize, len: usize| {
+        0.5 - 0.5 * (std::f32::consts::PI * (idx as f32) / (len as f32)).cos()
+    };
+    let samples = s.as_mut_slice();
+    for (idx, sample) in samples.iter_mut().take(fade_in.value()).enumerate() {
+        *sample *= ramp(idx, fade_in.value());
+    }
+    for (idx, sample) in samples.iter_mut().rev().take(fade_out.value()).enumerate() {
+        *sample *= ramp(idx, fade_out.value());
+    }
+}
+
+/// Largest absolute sample value
+pub fn peak(s: Samples) -> Amplitude {
+    Amplitude::new(s.as_slice().iter().fold(0.0f32, |acc, x| acc.max(x.abs())))
+}
+
+/// Root mean square of the samples
+pub fn rms(s: Samples) -> Amplitude {
+    let len = s.as_slice().len();
+    if len == 0 {
+        return Amplitude::zero();
+    }
+    let sum_of_squares: f32 = s.as_slice().iter().map(|x| x * x).sum();
+    Amplitude::new((sum_of_squares / (len as f32)).sqrt())
+}
+
+/// Summary of the sample values of a buffer
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SampleStats {
+    pub mean: Amplitude,
+    /// Population standard deviation
+    pub std_dev: Amplitude,
+    pub min: Amplitude,
+    pub max: Amplitude,
+    pub rms: Amplitude,
+}
+
+/// Mean, standard deviation, extremes and RMS of the samples in a single pass, all zero for an
+/// empty buffer
+pub fn stats(s: Samples) -> SampleStats {
+    let len = s.as_slice().len();
+    if len == 0 {
+        return SampleStats {
+            mean: Amplitude::zero(),
+            std_dev: Amplitude::zero(),
+            min: Amplitude::zero(),
+            max: Amplitude::zero(),
+            rms: Amplitude::zero(),
+        };
+    }
+    let (sum, sum_of_squares, min, max) = s.as_slice().iter().fold(
+        (0.0f32, 0.0f32, f32::MAX, f32::MIN),
+        |(sum, sum_of_squares, min, max), x| {
+            (sum + x, sum_of_squares + x * x, min.min(*x), max.max(*x))
+        },
+    );
+    let mean = sum / (len as f32);
+    let mean_square = sum_of_squares / (len as f32);
+    SampleStats {
+        mean: Amplitude::new(mean),
+        std_dev: Amplitude::new((mean_square - mean * mean).max(0.0).sqrt()),
+        min: Amplitude::new(min),
+        max: Amplitude::new(max),
+        rms: Amplitude::new(mean_square.sqrt()),
+    }
+}
+
+/// Ratio of the peak to the RMS value, zero for silent input
+pub fn crest_factor(s: Samples) -> Proportion {
+    let rms = rms(s);
+    if rms == Amplitude::zero() {
+        return Proportion::new(0.0);
+    }
+    Proportion::new(peak(s).value() / rms.value())
+}
+
+/// Estimates the fundamental frequency by counting sign changes around the mean of the samples
+pub fn zero_crossing_rate(s: Samples, rate: SamplingRate) -> Frequency {
+    let samples = s.as_slice();
+    if samples.len() < 2 {
+        return Frequency::new(0.0);
+    }
+    let mean = samples.iter().sum::<f32>() / (samples.len() as f32);
+    let crossings = samples
+        .windows(2)
+        .filter(|pair| (pair[0] < mean) != (pair[1] < mean))
+        .count();
+    let duration = (samples.len() as f32) / (rate.value() as f32);
+    Frequency::new((crossings as f32) / (2.0 * duration))
+}
+
+/// Renders the samples as a block character waveform of `height` rows of `width` columns
+///
+/// Each column covers an equal share of the buffer and is filled in the rows its minimum to
+/// maximum range reaches, with the vertical axis scaled to the buffer's peak.
+pub fn ascii_plot(s: Samples, width: usize, height: usize) -> String {
+    let samples = s.as_slice();
+    let scale = peak(s).value().max(f32::MIN_POSITIVE);
+    let row_height = 2.0 * scale / (height as f32);
+
+    let columns: Vec<Option<(f32, f32)>> = (0..width)
+        .map(|column| {
+            let start = column * samples.len() / width;
+            let end = ((column + 1) * samples.len() / width).max(start + 1);
+            samples
+                .get(start..end.min(samples.len()))
+                .and_then(|chunk| {
+                    chunk.iter().fold(None, |acc, x| match acc {
+                        None => Some((*x, *x)),
+                        Some((min, max)) => Some((min.min(*x), max.max(*x))),
+                    })
+                })
+        })
+        .collect();
+
+    (0..height)
+        .map(|row| {
+            let top = scale - row_height * (row as f32);
+            let bottom = top - row_height;
+            columns
+                .iter()
+                .map(|range| match range {
+                    Some((min, max)) if *min <= top && *max >= bottom => '█',
+                    _ => ' ',
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// First index at which the two buffers differ by more than `epsilon`, with the two values there
+///
+/// Buffers of different lengths are rejected, as they cannot match sample for sample.
+pub fn first_divergence(
+    a: Samples,
+    b: Samples,
+    epsilon: f32,
+) -> Result<Option<(usize, f32, f32)>, Error> {
+    if a.as_slice().len() != b.as_slice().len() {
+        return Err(Error::LengthMismatch);
+    }
+    Ok(a.as_slice()
+        .iter()
+        .zip(b.as_slice().iter())
+        .enumerate()
+        .find(|(_, (a, b))| (*a - *b).abs() > epsilon)
+        .map(|(idx, (a, b))| (idx, *a, *b)))
+}
+
+/// Splits an interleaved multichannel buffer into one buffer per channel
+pub fn deinterleave(interleaved: &[f32], channels: usize) -> Result<Vec<Vec<f32>>, Error> {
+    if channels == 0 || !interleaved.len().is_multiple_of(channels) {
+        return Err(Error::LengthMismatch);
+    }
+    let mut result = vec![Vec::with_capacity(interleaved.len() / channels); channels];
+    for frame in interleaved.chunks_exact(channels) {
+        for (channel, sample) in result.iter_mut().zip(frame.iter()) {
+            channel.push(*sample);
+        }
+    }
+    Ok(result)
+}
+
+/// Sample by sample mean of aligned captures of the same transmission
+///
+/// Uncorrelated noise drops by the square root of the number of captures.
+pub fn coherent_average(captures: &[&[f32]]) -> Result<Vec<f32>, Error> {
+    let Some(first) = captures.first() else {
+        return Ok(Vec::new());
+    };
+    if captures.iter().any(|c| c.len() != first.len()) {
+        return Err(Error::LengthMismatch);
+    }
+    let mut result = vec![0.0f32; first.len()];
+    for capture in captures.iter() {
+        result
+            .iter_mut()
+            .zip(capture.iter())
+            .for_each(|(sum, sample)| *sum += sample);
+    }
+    let count = captures.len() as f32;
+    result.iter_mut().for_each(|sum| *sum /= count);
+    Ok(result)
+}
+
+/// Rational sampling rate converter
+///
+/// The rate ratio is reduced to `up / down`. The input is conceptually zero-stuffed by `up`,
+/// filtered by a Blackman windowed sinc lowpass at the lower of the two Nyquist frequencies and
+/// decimated by `down`, evaluating only the filter phase that contributes to each output sample.
+pub struct PolyphaseResampler {
+    up: usize,
+    down: usize,
+    phases: Vec<Vec<f32>>,
+    delay: usize,
+}
+
+impl PolyphaseResampler {
+    /// Number of input samples covered by each side of the interpolation kernel
+    const HALF_WIDTH: usize = 16;
+
+    pub fn new(from: SamplingRate, to: SamplingRate) -> Self {
+        let divisor = gcd(from.value(), to.value());
+        let (up, down) = (to.value() / divisor, from.value() / divisor);
+
+        let taps = 2 * Self::HALF_WIDTH * up.max(down) + 1;
+        let center = (taps - 1) as f32 / 2.0;
+        let cutoff = 0.5f32 / (up.max(down) as f32);
+        let kernel: Vec<f32> = (0..taps)
+            .map(|n| {
+                let x = n as f32 - center;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    let arg = 2.0 * std::f32::consts::PI * cutoff * x;
+                    arg.sin() / arg
+                };
+                let phase = 2.0 * std::f32::consts::PI * (n as f32) / ((taps - 1) as f32);
+                let window = 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+                (up as f32) * 2.0 * cutoff * sinc * window
+            })
+            .collect();
+
+        let phases = (0..up)
+            .map(|phase| kernel.iter().skip(phase).step_by(up).copied().collect())
+            .collect();
+
+        Self {
+            up,
+            down,
+            phases,
+            delay: (taps - 1) / 2,
+        }
+    }
+
+    pub fn process(&self, s: Samples) -> Vec<f32> {
+        let input = s.as_slice();
+        let output_len = (input.len() * self.up).div_ceil(self.down);
+
+        (0..output_len)
+            .map(|n| {
+                let upsampled_idx = n * self.down + self.delay;
+                let (base, phase) = (upsampled_idx / self.up, upsampled_idx % self.up);
+                self.phases[phase]
+                    .iter()
+                    .enumerate()
+                    .take_while(|(tap, _)| *tap <= base)
+                    .filter_map(|(tap, h)| input.get(base - tap).map(|x| h * x))
+                    .sum()
+            })
+            .collect()
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
 pub trait Sampleable: Send {
     fn sample_into_f32(&mut self, out: SamplesMut, rate: SamplingRate);
 }
 
-pub struct WaveSampler<T>(T);
+/// Samples a wave the same way [`SignalSampler`] samples it wrapped in a [`WaveSignal`]
+pub struct WaveSampler<T: Wave>(WaveSignal<T>);
 
-impl<T: Sized> WaveSampler<T> {
+impl<T: Wave> WaveSampler<T> {
     pub fn new(t: T) -> Self {
-        Self(t)
+        Self(WaveSignal::new(t))
     }
 }
 
-impl<T: crate::waves::Wave> Sampleable for WaveSampler<T> {
-    fn sample_into_f32(&mut self, out: SamplesMut, rate: SamplingRate) {
-        let length = rate.sample(SampleCount::from(out.0.len()));
+impl<T: Wave> Sampleable for WaveSampler<T> {
+    fn sample_into_f32(&mut self, mut out: SamplesMut, rate: SamplingRate) {
         let increment = rate.increment();
 
-        for (sample_idx, sample_value) in out.0.iter_mut().enumerate() {
-            let amplitude = self.0.value_at(increment * (sample_idx as f32));
+        for sample_value in out.as_mut_slice().iter_mut() {
+            // Playing back a wave never fails
+            let amplitude = self.0.advance_with(increment).unwrap_or(Amplitude::zero());
             *sample_value = amplitude.value();
         }
-
-        self.0.shift_mut(length);
     }
 }
 
@@ -70,10 +465,10 @@ impl<T: Sized> SignalSampler<T> {
 }
 
 impl<T: crate::signals::Signal> Sampleable for SignalSampler<T> {
-    fn sample_into_f32(&mut self, out: SamplesMut, rate: SamplingRate) {
+    fn sample_into_f32(&mut self, mut out: SamplesMut, rate: SamplingRate) {
         let increment = rate.increment();
 
-        for sample_value in out.0.iter_mut() {
+        for sample_value in out.as_mut_slice().iter_mut() {
             let amplitude = match self.0.advance_with(increment) {
                 Ok(amplitude) => amplitude,
                 Err(crate::signals::Error::Finished) => Amplitude::zero(),
@@ -84,9 +479,51 @@ impl<T: crate::signals::Signal> Sampleable for SignalSampler<T> {
     }
 }
 
+/// Downconverts `count` samples of a real source by the carrier into in-phase and quadrature
+/// baseband channels
+///
+/// The mixer products are lowpass filtered by a moving average over one carrier cycle, which
+/// cancels the double frequency terms. The first cycle of the output is still settling.
+pub fn sample_iq<S: Sampleable>(
+    source: &mut S,
+    rate: SamplingRate,
+    carrier: Frequency,
+    count: SampleCount,
+) -> (Vec<f32>, Vec<f32>) {
+    let mut input = vec![0.0; count.0];
+    source.sample_into_f32(input.as_mut_slice().into(), rate);
+
+    let mut in_phase = vec![0.0; count.0];
+    WaveSampler::new(Sine::new(
+        carrier,
+        carrier.cycle_time() * 0.25f32,
+        Amplitude::new(2.0),
+    ))
+    .sample_into_f32(in_phase.as_mut_slice().into(), rate);
+    let mut quadrature = vec![0.0; count.0];
+    WaveSampler::new(Sine::new(carrier, Time::zero(), Amplitude::new(-2.0)))
+        .sample_into_f32(quadrature.as_mut_slice().into(), rate);
+
+    for (x, (i, q)) in input
+        .iter()
+        .zip(in_phase.iter_mut().zip(quadrature.iter_mut()))
+    {
+        *i *= x;
+        *q *= x;
+    }
+
+    let cycle = ((rate.value() as f32) / carrier.value()).round().max(1.0) as usize;
+    for channel in [&mut in_phase, &mut quadrature] {
+        if let Some(mut lowpass) = MovingAverage::new(cycle) {
+            lowpass.process(channel.as_mut_slice().into());
+        }
+    }
+    (in_phase, quadrature)
+}
+
 pub struct CompositeSampler<F, S1, S2>
 where
-    F: Fn((&f32, &f32), &mut f32) -> () + Send,
+    F: Fn((&f32, &f32), &mut f32) + Send,
     S1: Sampleable,
     S2: Sampleable,
 {
@@ -97,13 +534,13 @@ where
 
 impl<F, S1, S2> CompositeSampler<F, S1, S2>
 where
-    F: Fn((&f32, &f32), &mut f32) -> () + Send,
+    F: Fn((&f32, &f32), &mut f32) + Send,
     S1: Sampleable,
     S2: Sampleable,
 {
     pub fn new(s1: S1, s2: S2, compositor: F) -> Self {
         Self {
-            compositor: compositor,
+            compositor,
             s: (s1, s2),
             buffer: (Vec::new(), Vec::new()),
         }
@@ -112,26 +549,388 @@ where
 
 impl<F, S1, S2> Sampleable for CompositeSampler<F, S1, S2>
 where
-    F: Fn((&f32, &f32), &mut f32) -> () + Send,
+    F: Fn((&f32, &f32), &mut f32) + Send,
     S1: Sampleable,
     S2: Sampleable,
 {
-    fn sample_into_f32(&mut self, out: SamplesMut, rate: SamplingRate) {
-        if out.0.len() != self.buffer.0.len() {
-            self.buffer.0.resize(out.0.len(), 0.0);
-            self.buffer.1.resize(out.0.len(), 0.0);
+    fn sample_into_f32(&mut self, mut out: SamplesMut, rate: SamplingRate) {
+        let len = out.as_slice().len();
+        if len != self.buffer.0.len() {
+            self.buffer.0.resize(len, 0.0);
+            self.buffer.1.resize(len, 0.0);
         }
 
         self.s
             .0
-            .sample_into_f32(SamplesMut(self.buffer.0.as_mut_slice()), rate);
+            .sample_into_f32(self.buffer.0.as_mut_slice().into(), rate);
         self.s
             .1
-            .sample_into_f32(SamplesMut(self.buffer.1.as_mut_slice()), rate);
+            .sample_into_f32(self.buffer.1.as_mut_slice().into(), rate);
 
-        out.0
+        out.as_mut_slice()
             .iter_mut()
             .zip(self.buffer.0.iter().zip(self.buffer.1.iter()))
             .for_each(|(out, s)| (self.compositor)(s, out));
     }
 }
+
+/// Runs the filter in place over everything the source samples, carrying its state from call to
+/// call
+pub struct Filtered<S: Sampleable, F: Filter> {
+    source: S,
+    filter: F,
+}
+
+impl<S: Sampleable, F: Filter> Filtered<S, F> {
+    pub fn new(source: S, filter: F) -> Self {
+        Self { source, filter }
+    }
+}
+
+impl<S: Sampleable, F: Filter> Sampleable for Filtered<S, F> {
+    fn sample_into_f32(&mut self, mut out: SamplesMut, rate: SamplingRate) {
+        self.source
+            .sample_into_f32(SamplesMut(out.as_mut_slice()), rate);
+        self.filter.process(out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{noise, tone};
+
+    #[test]
+    fn nyquist_normalizes_to_half() {
+        let rate = SamplingRate::new(44100);
+        assert_eq!(
+            rate.normalized(Frequency::new(22050.0)),
+            Proportion::new(0.5)
+        );
+    }
+
+    #[test]
+    fn nyquist_radians_per_sample_is_pi() {
+        let rate = SamplingRate::new(44100);
+        assert!(
+            (rate.radians_per_sample(Frequency::new(22050.0)) - std::f32::consts::PI).abs() < 1e-6
+        );
+    }
+
+    #[test]
+    fn raw_f32_round_trip() {
+        let input = [0.0f32, 0.5, -1.25, 3.0];
+        let mut out = Vec::new();
+        write_raw_f32(&mut out, input[..].into()).unwrap();
+        let read: Vec<f32> = out
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        assert_eq!(read, input);
+    }
+
+    #[test]
+    fn raw_i16_round_trip() {
+        let input = [0.0f32, 0.5, -1.0, 2.0];
+        let mut out = Vec::new();
+        write_raw_i16(&mut out, input[..].into()).unwrap();
+        let read: Vec<i16> = out
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        assert_eq!(read, vec![0, 16384, -32767, 32767]);
+    }
+
+    #[test]
+    fn samples_from_slices() {
+        let mut buffer = vec![1.0f32, 2.0, 3.0];
+        let samples: Samples = buffer.as_slice().into();
+        assert_eq!(samples.as_slice(), &[1.0, 2.0, 3.0]);
+
+        let mut samples_mut: SamplesMut = buffer.as_mut_slice().into();
+        samples_mut.as_mut_slice()[1] = 5.0;
+        assert_eq!(samples_mut.as_slice(), &[1.0, 5.0, 3.0]);
+        assert_eq!(buffer, vec![1.0, 5.0, 3.0]);
+    }
+
+    #[test]
+    fn hann_window_application() {
+        let mut window = vec![0.0f32; 33];
+        fill_hann(window.as_mut_slice().into());
+
+        let mut buffer = vec![1.0f32; 33];
+        apply_window(buffer.as_mut_slice().into(), &window).unwrap();
+        assert!(buffer[0].abs() < 1e-6);
+        assert!(buffer[32].abs() < 1e-6);
+        assert!((buffer[16] - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn hamming_window_endpoints() {
+        let mut window = vec![0.0f32; 9];
+        fill_hamming(window.as_mut_slice().into());
+        assert!((window[0] - 0.08).abs() < 1e-6);
+        assert!((window[8] - 0.08).abs() < 1e-6);
+        assert!((window[4] - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn windows_of_empty_buffer() {
+        let mut window: Vec<f32> = Vec::new();
+        fill_hann(window.as_mut_slice().into());
+        fill_hamming(window.as_mut_slice().into());
+        assert!(window.is_empty());
+    }
+
+    #[test]
+    fn window_length_mismatch() {
+        let mut buffer = vec![1.0f32; 4];
+        assert!(matches!(
+            apply_window(buffer.as_mut_slice().into(), &[1.0, 1.0]),
+            Err(Error::LengthMismatch)
+        ));
+    }
+
+    #[test]
+    fn iq_of_unmodulated_carrier() {
+        let rate = SamplingRate::new(48000);
+        let carrier = Frequency::new(1000.0);
+        let mut source = WaveSampler::new(Sine::new(carrier, Time::zero(), Amplitude::new(0.8)));
+        let (i, q) = sample_iq(&mut source, rate, carrier, SampleCount::from(960));
+
+        for (i, q) in i.iter().zip(q.iter()).skip(48) {
+            assert!(i.abs() < 1e-2);
+            assert!((q + 0.8).abs() < 1e-2);
+            assert!(((i * i + q * q).sqrt() - 0.8).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn crest_factor_of_sine_and_square() {
+        let rate = SamplingRate::new(48000);
+        let mut sine = vec![0.0f32; 4800];
+        WaveSampler::new(Sine::new(
+            Frequency::new(1000.0),
+            Time::zero(),
+            Amplitude::new(0.5),
+        ))
+        .sample_into_f32(sine.as_mut_slice().into(), rate);
+        assert!((crest_factor(Samples(&sine)).value() - std::f32::consts::SQRT_2).abs() < 1e-3);
+
+        let square: Vec<f32> = sine.iter().map(|x| 0.5f32.copysign(*x)).collect();
+        assert!((crest_factor(Samples(&square)).value() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn crest_factor_of_silence() {
+        assert_eq!(crest_factor(Samples(&[0.0; 16])), Proportion::new(0.0));
+    }
+
+    #[test]
+    fn deinterleave_stereo() {
+        let channels = deinterleave(&[0.0, 1.0, 0.5, 1.5, -0.5, -1.0], 2).unwrap();
+        assert_eq!(channels, vec![vec![0.0, 0.5, -0.5], vec![1.0, 1.5, -1.0]]);
+    }
+
+    #[test]
+    fn deinterleave_incomplete_frame() {
+        assert!(matches!(
+            deinterleave(&[0.0, 1.0, 0.5], 2),
+            Err(Error::LengthMismatch)
+        ));
+    }
+
+    #[test]
+    fn polyphase_resampling_preserves_frequency() {
+        let (from, to) = (SamplingRate::new(44100), SamplingRate::new(48000));
+        let mut input = vec![0.0f32; 44100];
+        WaveSampler::new(Sine::new(
+            Frequency::new(1000.0),
+            Time::zero(),
+            Amplitude::new(0.5),
+        ))
+        .sample_into_f32(input.as_mut_slice().into(), from);
+
+        let output = PolyphaseResampler::new(from, to).process(Samples(&input));
+        assert_eq!(output.len(), 48000);
+
+        let frequency =
+            crate::proc::peak_frequency(Samples(&output), to, &mut rustfft::FftPlanner::new());
+        assert_eq!(frequency, Frequency::new(1000.0));
+        assert!((peak(Samples(&output[1000..47000])).value() - 0.5).abs() < 1e-2);
+    }
+
+    #[test]
+    fn zero_crossing_rate_of_biased_sine() {
+        let rate = SamplingRate::new(48000);
+        let mut sine = vec![0.0f32; 48000];
+        WaveSampler::new(Sine::new(
+            Frequency::new(440.0),
+            Time::new(0.0001),
+            Amplitude::new(0.5),
+        ))
+        .sample_into_f32(sine.as_mut_slice().into(), rate);
+        sine.iter_mut().for_each(|x| *x += 0.3);
+
+        assert!((zero_crossing_rate(Samples(&sine), rate).value() - 440.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn ascii_plot_of_sine() {
+        let rate = SamplingRate::new(8000);
+        let mut sine = vec![0.0f32; 800];
+        WaveSampler::new(Sine::new(
+            Frequency::new(40.0),
+            Time::zero(),
+            Amplitude::new(1.0),
+        ))
+        .sample_into_f32(sine.as_mut_slice().into(), rate);
+
+        let plot = ascii_plot(Samples(&sine), 40, 8);
+        let rows: Vec<&str> = plot.lines().collect();
+        assert_eq!(rows.len(), 8);
+        assert!(rows.iter().all(|row| row.chars().count() == 40));
+
+        // Four cycles: the crests reach the top row and the troughs the bottom row, half a cycle apart
+        let top: Vec<char> = rows[0].chars().collect();
+        let bottom: Vec<char> = rows[7].chars().collect();
+        for cycle in 0..4 {
+            assert_eq!(top[cycle * 10 + 2], '█');
+            assert_eq!(top[cycle * 10 + 7], ' ');
+            assert_eq!(bottom[cycle * 10 + 7], '█');
+            assert_eq!(bottom[cycle * 10 + 2], ' ');
+        }
+    }
+
+    #[test]
+    fn first_divergence_of_buffers() {
+        let a = [0.0f32, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7];
+        assert!(matches!(
+            first_divergence(Samples(&a), Samples(&a), 1e-6),
+            Ok(None)
+        ));
+
+        let mut b = a;
+        b[3] += 1e-7;
+        b[5] = 0.9;
+        b[6] = -0.6;
+        assert!(matches!(
+            first_divergence(Samples(&a), Samples(&b), 1e-6),
+            Ok(Some((5, x, y))) if x == 0.5 && y == 0.9
+        ));
+        assert!(matches!(
+            first_divergence(Samples(&a), Samples(&a[..7]), 1e-6),
+            Err(Error::LengthMismatch)
+        ));
+    }
+
+    #[test]
+    fn filtered_tone_above_cutoff_is_attenuated() {
+        let rate = SamplingRate::new(8000);
+        let filtered = |freq: f32| {
+            let sine = Sine::new(Frequency::new(freq), Time::zero(), Amplitude::new(1.0));
+            let mut sampler = Filtered::new(WaveSampler::new(sine), MovingAverage::new(8).unwrap());
+            let mut out = vec![0.0f32; 800];
+            let (first, second) = out.split_at_mut(333);
+            sampler.sample_into_f32(SamplesMut(first), rate);
+            sampler.sample_into_f32(SamplesMut(second), rate);
+            peak(Samples(&out[8..])).value()
+        };
+
+        assert!(filtered(50.0) > 0.95);
+        assert!(filtered(1000.0) < 1e-3);
+    }
+
+    #[test]
+    fn coherent_average_reduces_noise() {
+        let clean = tone(160.0, 8000, 1000);
+        let captures: Vec<Vec<f32>> = (0..16)
+            .map(|seed| {
+                clean
+                    .iter()
+                    .zip(noise(clean.len(), seed).iter())
+                    .map(|(x, n)| x + 0.5 * n)
+                    .collect()
+            })
+            .collect();
+        let error = |count: usize| {
+            let refs: Vec<&[f32]> = captures[..count].iter().map(|c| c.as_slice()).collect();
+            let averaged = coherent_average(&refs).unwrap();
+            let diff: Vec<f32> = averaged
+                .iter()
+                .zip(clean.iter())
+                .map(|(a, c)| a - c)
+                .collect();
+            rms(Samples(&diff)).value()
+        };
+
+        assert!(error(4) < 0.6 * error(1));
+        assert!(error(16) < 0.6 * error(4));
+        assert!(matches!(
+            coherent_average(&[&clean, &clean[1..]]),
+            Err(Error::LengthMismatch)
+        ));
+    }
+
+    #[test]
+    fn fade_ramps_in_and_out() {
+        let mut s = vec![0.8f32; 100];
+        fade(
+            SamplesMut(&mut s),
+            SampleCount::from(10),
+            SampleCount::from(20),
+        );
+
+        assert_eq!(s[0], 0.0);
+        assert_eq!(s[99], 0.0);
+        assert!((s[5] - 0.4).abs() < 1e-6);
+        assert!((s[89] - 0.4).abs() < 1e-6);
+        assert!(s[..10].windows(2).all(|w| w[0] < w[1]));
+        assert!(s[80..].windows(2).all(|w| w[0] > w[1]));
+        assert!(s[10..=79].iter().all(|x| *x == 0.8));
+    }
+
+    #[test]
+    fn windows_with_hop_overlap() {
+        let s = [0.0f32, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0];
+        let windows: Vec<&[f32]> = Samples(&s)
+            .windows_hop(SampleCount::from(4), SampleCount::from(2))
+            .map(|w| w.as_slice())
+            .collect();
+        assert_eq!(windows, vec![&s[0..4], &s[2..6], &s[4..8]]);
+        assert_eq!(
+            Samples(&s)
+                .windows_hop(SampleCount::from(9), SampleCount::from(1))
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn stats_of_known_buffer() {
+        let s = [2.0f32, -1.0, 4.0, -1.0, 1.0];
+        let stats = stats(Samples(&s));
+        // Mean 1, squares sum to 23, deviations squared sum to 18
+        assert_eq!(stats.mean, Amplitude::new(1.0));
+        assert!((stats.std_dev.value() - (18.0f32 / 5.0).sqrt()).abs() < 1e-6);
+        assert_eq!(stats.min, Amplitude::new(-1.0));
+        assert_eq!(stats.max, Amplitude::new(4.0));
+        assert!((stats.rms.value() - (23.0f32 / 5.0).sqrt()).abs() < 1e-6);
+        assert_eq!(stats.rms, rms(Samples(&s)));
+    }
+
+    #[test]
+    fn carrier_above_nyquist_rejected() {
+        let rate = SamplingRate::new(44100);
+        assert_eq!(rate.max_frequency(), Frequency::new(22050.0));
+        assert!(check_nyquist(Frequency::new(20000.0), rate).is_ok());
+        assert_eq!(
+            check_nyquist(Frequency::new(22050.0), rate),
+            Err(Frequency::new(22050.0))
+        );
+        assert_eq!(
+            check_nyquist(Frequency::new(25000.0), rate),
+            Err(Frequency::new(25000.0))
+        );
+    }
+}
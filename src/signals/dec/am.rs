@@ -1,8 +1,1024 @@
-use crate::units::Time;
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
 
+use crate::sampling::{SampleCount, Samples, SamplesMut, SamplingRate};
+use crate::units::{Amplitude, Decibel, Frequency, Proportion, Time};
+
+#[allow(dead_code)]
 struct Parameters {
     transition_width: Time,
     baud_length: Time,
 }
 
+#[allow(dead_code)]
 struct Bitstream {}
+
+/// Mean and standard deviation in samples of the detected transitions' deviation from the ideal
+/// baud grid, which is anchored at the first transition
+///
+/// Both are zero without transitions or for a non-positive `samples_per_baud`, which defines no
+/// grid to deviate from.
+pub fn measure_jitter(transitions_samples: &[usize], samples_per_baud: f32) -> (f32, f32) {
+    let Some(&first) = transitions_samples.first() else {
+        return (0.0, 0.0);
+    };
+    if samples_per_baud.is_nan() || samples_per_baud <= 0.0 {
+        return (0.0, 0.0);
+    }
+
+    let deviations: Vec<f32> = transitions_samples
+        .iter()
+        .map(|&t| {
+            let elapsed = t as f32 - first as f32;
+            elapsed - (elapsed / samples_per_baud).round() * samples_per_baud
+        })
+        .collect();
+
+    let count = deviations.len() as f32;
+    let mean = deviations.iter().sum::<f32>() / count;
+    let variance = deviations.iter().map(|d| (d - mean).powi(2)).sum::<f32>() / count;
+    (mean, variance.sqrt())
+}
+
+/// Offset of the strongest carrier in the capture from the nominal carrier frequency, zero for an
+/// empty capture
+pub fn estimate_frequency_offset(
+    s: Samples,
+    nominal: Frequency,
+    rate: SamplingRate,
+    fft: &mut FftPlanner<f32>,
+) -> Frequency {
+    if s.as_slice().is_empty() {
+        return Frequency::new(0.0);
+    }
+    let peak = crate::proc::peak_frequency(s, rate, fft);
+    Frequency::new(peak.value() - nominal.value())
+}
+
+/// Analytic signal of the real samples, whose magnitude is the instantaneous amplitude and whose
+/// argument is the instantaneous phase of the input
+///
+/// Its real part is the input itself, its imaginary part the Hilbert transform of it.
+pub fn analytic_signal(s: Samples, fft: &mut FftPlanner<f32>) -> Vec<Complex<f32>> {
+    let len = s.as_slice().len();
+    let mut bins = crate::proc::spectrum(s, fft);
+    for (idx, bin) in bins.iter_mut().enumerate() {
+        let scale = if idx == 0 || 2 * idx == len {
+            1.0
+        } else if 2 * idx < len {
+            2.0
+        } else {
+            0.0
+        };
+        *bin *= scale / (len as f32);
+    }
+    fft.plan_fft_inverse(len).process(&mut bins);
+    bins
+}
+
+/// Peak amplitude of the carrier in the capture, independent of any gain the processing applied
+/// to the spectrum
+///
+/// The energy of the Hann windowed spectrum within three bins of the carrier is normalized by the
+/// window's power, so the result does not depend on where the carrier falls between bins.
+pub fn measure_carrier_amplitude(
+    s: Samples,
+    carrier: Frequency,
+    rate: SamplingRate,
+    fft: &mut FftPlanner<f32>,
+) -> Amplitude {
+    let len = s.as_slice().len();
+    if len == 0 {
+        return Amplitude::zero();
+    }
+    let bins = crate::proc::windowed_spectrum(s, fft);
+    let center = (carrier.value() * (len as f32) / (rate.value() as f32)).round() as usize;
+    let energy: f32 = bins
+        .iter()
+        .take(len / 2 + 1)
+        .skip(center.saturating_sub(3))
+        .take(7)
+        .map(|bin| bin.norm_sqr())
+        .sum();
+
+    let mut window = vec![0.0f32; len];
+    crate::sampling::fill_hann(SamplesMut(&mut window));
+    let window_power: f32 = window.iter().map(|w| w * w).sum();
+    Amplitude::new((4.0 * energy / ((len as f32) * window_power)).sqrt())
+}
+
+/// Amplitude of the carrier remaining in a region of the capture where it should be keyed off
+pub fn carrier_leakage(
+    s: Samples,
+    carrier: Frequency,
+    rate: SamplingRate,
+    fft: &mut FftPlanner<f32>,
+    off_region: std::ops::Range<usize>,
+) -> Amplitude {
+    let samples = s.as_slice();
+    let end = off_region.end.min(samples.len());
+    let start = off_region.start.min(end);
+    measure_carrier_amplitude(Samples(&samples[start..end]), carrier, rate, fft)
+}
+
+/// Why a capture does not match the configured sampling rate
+#[derive(Debug, PartialEq)]
+pub enum RateMismatch {
+    /// The capture holds no carrier to measure the rate by
+    NoCarrier,
+    /// The carrier is off, as if the capture was recorded at the given rate
+    Implied(Frequency),
+}
+
+/// Checks that the strongest carrier of the capture is where it is expected at the configured rate
+///
+/// If it is off by more than a percent, the sampling rate that would put it at the expected
+/// frequency is returned, as the capture was most likely recorded at that rate. An empty or
+/// constant capture, or one whose strongest bin is 60 dB below its total energy, has no carrier.
+pub fn check_rate(
+    s: Samples,
+    expected_carrier: Frequency,
+    configured_rate: SamplingRate,
+    fft: &mut FftPlanner<f32>,
+) -> Result<(), RateMismatch> {
+    let len = s.as_slice().len();
+    let powers: Vec<f32> = crate::proc::spectrum(s, fft)
+        .iter()
+        .take(len / 2 + 1)
+        .map(|bin| bin.norm_sqr())
+        .collect();
+    let total: f32 = powers.iter().sum();
+    let Some((peak, power)) = powers
+        .iter()
+        .enumerate()
+        .skip(1)
+        .max_by(|a, b| a.1.total_cmp(b.1))
+    else {
+        return Err(RateMismatch::NoCarrier);
+    };
+    if *power <= 1e-6 * total {
+        return Err(RateMismatch::NoCarrier);
+    }
+
+    let measured = (peak * configured_rate.value()) as f32 / (len as f32);
+    if (measured - expected_carrier.value()).abs() <= 0.01 * expected_carrier.value() {
+        Ok(())
+    } else {
+        Err(RateMismatch::Implied(Frequency::new(
+            (configured_rate.value() as f32) * expected_carrier.value() / measured,
+        )))
+    }
+}
+
+/// Ratio of the target channel's energy to the neighbor channel's energy left after band filtering
+/// around the target
+///
+/// The band filter is modelled as a second order Butterworth bandpass of the given bandwidth
+/// centered on the target. Spectral energy nearer to the target than to the neighbor counts as
+/// the target channel's, the rest as the neighbor's. Without energy in either channel, as in an
+/// empty or silent capture or one where nothing leaks, there is no ratio and `None` is returned.
+pub fn channel_isolation(
+    s: Samples,
+    target: Frequency,
+    neighbor: Frequency,
+    bandwidth: Frequency,
+    rate: SamplingRate,
+    fft: &mut FftPlanner<f32>,
+) -> Option<Decibel> {
+    let len = s.as_slice().len();
+    let bins = crate::proc::windowed_spectrum(s, fft);
+    let bin_width = (rate.value() as f32) / (len as f32);
+    let midpoint = (target.value() + neighbor.value()) / 2.0;
+    let target_below = target.value() < neighbor.value();
+
+    let (mut own, mut leaked) = (0.0f32, 0.0f32);
+    for (idx, bin) in bins.iter().enumerate().take(len / 2 + 1) {
+        let frequency = idx as f32 * bin_width;
+        let detuning = (frequency - target.value()) / (bandwidth.value() / 2.0);
+        let filtered = bin.norm_sqr() / (1.0 + detuning.powi(4));
+        if (frequency < midpoint) == target_below {
+            own += filtered;
+        } else {
+            leaked += filtered;
+        }
+    }
+    if own == 0.0 || leaked == 0.0 {
+        return None;
+    }
+    Some(Decibel::from_power_ratio(own / leaked))
+}
+
+/// Shifts the whole spectrum of the capture down by `offset`
+///
+/// The shift is done on the analytic signal, so unlike plain mixing with a real oscillator it does
+/// not leave an image at the mirrored frequency.
+pub fn correct_frequency_offset(mut s: SamplesMut, offset: Frequency, rate: SamplingRate) {
+    let analytic = analytic_signal(Samples(s.as_slice()), &mut FftPlanner::new());
+    let increment = -2.0f32 * std::f32::consts::PI * rate.normalized(offset).value();
+    for (idx, (sample, a)) in s.as_mut_slice().iter_mut().zip(analytic).enumerate() {
+        let phase = increment * (idx as f32);
+        *sample = a.re * phase.cos() - a.im * phase.sin();
+    }
+}
+
+/// Estimates the symbol rate from the gaps between detected transitions
+///
+/// The smallest gap length that occurs commonly (in at least a tenth of the gaps, allowing one
+/// sample of jitter) is taken as a first guess of the baud period, which is then refined by
+/// fitting every gap to its nearest whole number of periods. Returns zero if there are fewer than
+/// two distinct transitions.
+pub fn symbol_rate_from_intervals(transition_samples: &[usize], rate: SamplingRate) -> Frequency {
+    let gaps: Vec<usize> = transition_samples
+        .windows(2)
+        .map(|pair| pair[1].saturating_sub(pair[0]))
+        .filter(|gap| *gap > 0)
+        .collect();
+
+    let mut histogram = std::collections::BTreeMap::new();
+    for gap in gaps.iter() {
+        *histogram.entry(*gap).or_insert(0usize) += 1;
+    }
+
+    let threshold = (gaps.len() / 10).max(1);
+    let Some(guess) = histogram.keys().copied().find(|gap| {
+        histogram
+            .range(gap.saturating_sub(1)..=gap + 1)
+            .map(|(_, count)| count)
+            .sum::<usize>()
+            >= threshold
+    }) else {
+        return Frequency::new(0.0);
+    };
+
+    let periods: f32 = gaps
+        .iter()
+        .map(|gap| ((*gap as f32) / (guess as f32)).round().max(1.0))
+        .sum();
+    let period = (gaps.iter().sum::<usize>() as f32) / periods;
+    Frequency::new((rate.value() as f32) / period)
+}
+
+/// Classic AM modulation depth `(max - min) / (max + min)` of the detected envelope
+///
+/// An empty envelope, or one that never rises above zero, has no depth and yields zero.
+pub fn modulation_index(envelope: Samples) -> Proportion {
+    if envelope.as_slice().is_empty() {
+        return Proportion::new(0.0);
+    }
+    let (min, max) = envelope
+        .as_slice()
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), x| {
+            (min.min(*x), max.max(*x))
+        });
+    if max + min <= 0.0 {
+        return Proportion::new(0.0);
+    }
+    Proportion::new((max - min) / (max + min))
+}
+
+/// Whether the envelope dips below the floor, which for a signal whose low level should stay above
+/// it indicates over-modulation distortion
+pub fn is_overmodulated(envelope: Samples, floor: Amplitude) -> bool {
+    envelope.as_slice().iter().any(|x| *x < floor.value())
+}
+
+/// Amplitude threshold best separating the envelope into a low and a high class by Otsu's method
+///
+/// The envelope's range is divided into `bins` equal bins, and the threshold is placed at the bin
+/// edge that maximizes the between-class variance.
+pub fn otsu_threshold(envelope: Samples, bins: usize) -> Amplitude {
+    let samples = envelope.as_slice();
+    let (min, max) = samples
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), x| {
+            (min.min(*x), max.max(*x))
+        });
+    if samples.is_empty() || bins == 0 || min >= max {
+        return Amplitude::new(if samples.is_empty() { 0.0 } else { min });
+    }
+
+    let width = (max - min) / (bins as f32);
+    let mut histogram = vec![0usize; bins];
+    for x in samples.iter() {
+        histogram[(((x - min) / width) as usize).min(bins - 1)] += 1;
+    }
+
+    let center = |bin: usize| min + width * (bin as f32 + 0.5);
+    let total = samples.len() as f32;
+    let total_sum: f32 = histogram
+        .iter()
+        .enumerate()
+        .map(|(bin, count)| center(bin) * (*count as f32))
+        .sum();
+
+    let (mut low_count, mut low_sum) = (0.0f32, 0.0f32);
+    let between_variances: Vec<f32> = histogram
+        .iter()
+        .enumerate()
+        .take(bins - 1)
+        .map(|(bin, count)| {
+            low_count += *count as f32;
+            low_sum += center(bin) * (*count as f32);
+            let high_count = total - low_count;
+            if low_count == 0.0 || high_count == 0.0 {
+                return 0.0;
+            }
+            let mean_difference = low_sum / low_count - (total_sum - low_sum) / high_count;
+            low_count * high_count * mean_difference * mean_difference
+        })
+        .collect();
+
+    // Empty bins between the classes leave a plateau of maxima, split it in the middle
+    let best = between_variances.iter().copied().fold(0.0f32, f32::max);
+    let on_plateau = |v: &f32| *v >= best * (1.0 - 1e-6);
+    let first = between_variances.iter().position(on_plateau).unwrap_or(0);
+    let last = between_variances.iter().rposition(on_plateau).unwrap_or(0);
+    Amplitude::new(min + width * ((first + last) as f32 / 2.0 + 1.0))
+}
+
+/// Sum of squares of the envelope over each whole bit period starting at `offset`
+pub fn bit_energies(envelope: Samples, samples_per_baud: usize, offset: usize) -> Vec<f32> {
+    if samples_per_baud == 0 {
+        return Vec::new();
+    }
+    envelope
+        .as_slice()
+        .get(offset..)
+        .unwrap_or(&[])
+        .chunks_exact(samples_per_baud)
+        .map(|bit| bit.iter().map(|x| x * x).sum())
+        .collect()
+}
+
+/// Per-bin noise magnitude estimated from the signal-free lead-in of the capture
+///
+/// Bins are returned from DC up to Nyquist and scaled by `1/sqrt(N)`, so for white noise each bin
+/// approximates the noise's RMS level.
+pub fn noise_profile(
+    s: Samples,
+    lead_in: SampleCount,
+    fft: &mut FftPlanner<f32>,
+) -> Vec<Amplitude> {
+    let samples = s.as_slice();
+    let lead_in = &samples[..lead_in.value().min(samples.len())];
+    let scale = (lead_in.len() as f32).sqrt().recip();
+    crate::proc::spectrum(Samples(lead_in), fft)
+        .iter()
+        .take(lead_in.len() / 2 + 1)
+        .map(|bin| Amplitude::new(bin.norm() * scale))
+        .collect()
+}
+
+/// Noise floor estimated as the given percentile of the sample magnitudes
+///
+/// Low percentiles reflect the quiet baseline even while a signal is present in part of the
+/// capture.
+pub fn noise_floor_percentile(s: Samples, percentile: Proportion) -> Amplitude {
+    let mut magnitudes: Vec<f32> = s.as_slice().iter().map(|x| x.abs()).collect();
+    if magnitudes.is_empty() {
+        return Amplitude::zero();
+    }
+    magnitudes.sort_by(|a, b| a.total_cmp(b));
+    let idx = (percentile.value().clamp(0.0, 1.0) * ((magnitudes.len() - 1) as f32)).round();
+    Amplitude::new(magnitudes[idx as usize])
+}
+
+/// Signal level that stands `required_snr` above the noise floor
+pub fn minimum_detectable_level(noise_floor: Amplitude, required_snr: Decibel) -> Amplitude {
+    noise_floor * required_snr.amplitude_ratio()
+}
+
+/// The part of the capture a frame starting at `start` spans, estimated from the number of
+/// symbols the frame's parameters encode to
+///
+/// The view is cut short if the capture ends before the frame does.
+pub fn extract_frame<'a>(
+    s: Samples<'a>,
+    start: usize,
+    baudrate: Frequency,
+    nrz_params: &crate::encodings::enc::nrz::Parameters,
+    rate: SamplingRate,
+) -> Samples<'a> {
+    let symbols = nrz_params.expected_transition_count() as f32;
+    let len = (symbols * (rate.value() as f32) / baudrate.value()).round() as usize;
+    let samples = s.as_slice();
+    let start = start.min(samples.len());
+    Samples(&samples[start..(start + len).min(samples.len())])
+}
+
+/// Likely transition centers, found as the middles of the runs where the envelope changes by more
+/// than `threshold` from one sample to the next
+pub fn symbol_boundaries(envelope: Samples, threshold: Amplitude) -> Vec<usize> {
+    let mut boundaries = Vec::new();
+    let mut run_start = None;
+    for (idx, pair) in envelope.as_slice().windows(2).enumerate() {
+        let steep = (pair[1] - pair[0]).abs() > threshold.value();
+        match (steep, run_start) {
+            (true, None) => run_start = Some(idx + 1),
+            (false, Some(start)) => {
+                boundaries.push((start + idx) / 2);
+                run_start = None;
+            },
+            _ => (),
+        }
+    }
+    if let Some(start) = run_start {
+        boundaries.push((start + envelope.as_slice().len() - 1) / 2);
+    }
+    boundaries
+}
+
+/// Transition width as a proportion of the baud period, estimated from the mean 10-90% rise and
+/// fall time of the envelope's edges
+///
+/// Edges are assumed linear, so the measured time is scaled up to the full transition. Returns
+/// zero if no complete edge is found.
+pub fn estimate_transition_width(
+    envelope: Samples,
+    rate: SamplingRate,
+    baud: Frequency,
+) -> Proportion {
+    let samples = envelope.as_slice();
+    let (min, max) = samples
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(lo, hi), x| (lo.min(*x), hi.max(*x)));
+    if samples.len() < 2 || max <= min {
+        return Proportion::new(0.0);
+    }
+    let (low, high) = (min + 0.1 * (max - min), min + 0.9 * (max - min));
+
+    // Crossings of either reference level as (interpolated sample position, level, rising)
+    let mut crossings = Vec::new();
+    for (idx, pair) in samples.windows(2).enumerate() {
+        for level in [low, high] {
+            if (pair[0] < level) != (pair[1] < level) {
+                let position = idx as f32 + (level - pair[0]) / (pair[1] - pair[0]);
+                crossings.push((position, level, pair[1] > pair[0]));
+            }
+        }
+    }
+    crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    // A complete edge crosses one level then the other in the same direction, starting from the low
+    // level when rising and from the high level when falling
+    let edge_lengths: Vec<f32> = crossings
+        .windows(2)
+        .filter(|pair| {
+            pair[0].2 == pair[1].2 && pair[0].1 != pair[1].1 && (pair[0].1 == low) == pair[0].2
+        })
+        .map(|pair| pair[1].0 - pair[0].0)
+        .collect();
+    if edge_lengths.is_empty() {
+        return Proportion::new(0.0);
+    }
+
+    let mean = edge_lengths.iter().sum::<f32>() / (edge_lengths.len() as f32);
+    let samples_per_baud = (rate.value() as f32) / baud.value();
+    Proportion::new(mean / 0.8 / samples_per_baud)
+}
+
+/// Least squares `(gain, offset)` mapping the start of the capture onto the known preamble, to be
+/// applied as `gain * sample + offset` to the rest of the frame
+///
+/// A capture that is constant over the preamble only has its offset corrected.
+pub fn calibrate_from_preamble(s: Samples, preamble_template: &[f32]) -> (f32, f32) {
+    let pairs: Vec<(f32, f32)> = s
+        .as_slice()
+        .iter()
+        .copied()
+        .zip(preamble_template.iter().copied())
+        .collect();
+    if pairs.is_empty() {
+        return (1.0, 0.0);
+    }
+
+    let count = pairs.len() as f32;
+    let mean_captured = pairs.iter().map(|p| p.0).sum::<f32>() / count;
+    let mean_template = pairs.iter().map(|p| p.1).sum::<f32>() / count;
+    let covariance: f32 = pairs
+        .iter()
+        .map(|p| (p.0 - mean_captured) * (p.1 - mean_template))
+        .sum();
+    let variance: f32 = pairs.iter().map(|p| (p.0 - mean_captured).powi(2)).sum();
+    let gain = if variance > 0.0 {
+        covariance / variance
+    } else {
+        1.0
+    };
+    (gain, mean_template - gain * mean_captured)
+}
+
+/// Compensates a linear fade across the envelope in place, returning the fitted `(slope,
+/// intercept)` in amplitude per sample and amplitude
+///
+/// A line is fitted to the peaks of sixteen equal blocks of the envelope, and every sample is
+/// scaled so that the line becomes level at its intercept.
+pub fn detrend_amplitude(mut envelope: SamplesMut) -> (f32, f32) {
+    const BLOCKS: usize = 16;
+    let samples = envelope.as_mut_slice();
+    if samples.is_empty() {
+        return (0.0, 0.0);
+    }
+
+    let block_len = samples.len().div_ceil(BLOCKS);
+    let peaks: Vec<(f32, f32)> = samples
+        .chunks(block_len)
+        .enumerate()
+        .filter_map(|(block, chunk)| {
+            chunk
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map(|(idx, x)| ((block * block_len + idx) as f32, *x))
+        })
+        .collect();
+
+    let count = peaks.len() as f32;
+    let mean_x = peaks.iter().map(|p| p.0).sum::<f32>() / count;
+    let mean_y = peaks.iter().map(|p| p.1).sum::<f32>() / count;
+    let covariance: f32 = peaks.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    let variance: f32 = peaks.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    let slope = if variance > 0.0 {
+        covariance / variance
+    } else {
+        0.0
+    };
+    let intercept = mean_y - slope * mean_x;
+
+    for (idx, sample) in samples.iter_mut().enumerate() {
+        let trend = intercept + slope * (idx as f32);
+        if trend > 0.0 {
+            *sample *= intercept / trend;
+        }
+    }
+    (slope, intercept)
+}
+
+/// Streaming level estimate replacing each sample with the RMS of the trailing window
+///
+/// Until the window has filled, the missing history counts as silence.
+pub struct RmsFollower {
+    squares: std::collections::VecDeque<f32>,
+    sum: f32,
+}
+
+impl RmsFollower {
+    pub fn new(window: Time, rate: SamplingRate) -> Self {
+        let len = ((window.value() * (rate.value() as f32)).round() as usize).max(1);
+        Self {
+            squares: std::collections::VecDeque::from(vec![0.0; len]),
+            sum: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, mut s: SamplesMut) {
+        let len = self.squares.len() as f32;
+        for sample in s.as_mut_slice().iter_mut() {
+            let square = *sample * *sample;
+            self.sum += square - self.squares.pop_front().unwrap_or(0.0);
+            self.squares.push_back(square);
+            *sample = (self.sum.max(0.0) / len).sqrt();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{noise, tone};
+
+    #[test]
+    fn jitter_of_perfect_grid() {
+        let transitions = [100, 110, 130, 140, 170, 180];
+        let (mean, std_dev) = measure_jitter(&transitions, 10.0);
+        assert!(mean.abs() < 1e-6);
+        assert!(std_dev.abs() < 1e-6);
+    }
+
+    #[test]
+    fn jitter_without_baud_grid() {
+        let transitions = [100, 110, 130];
+        assert_eq!(measure_jitter(&transitions, 0.0), (0.0, 0.0));
+        assert_eq!(measure_jitter(&transitions, -10.0), (0.0, 0.0));
+        assert_eq!(measure_jitter(&[], 10.0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn jitter_of_perturbed_grid() {
+        let transitions = [100, 112, 128, 140, 172, 178];
+        let (mean, std_dev) = measure_jitter(&transitions, 10.0);
+        assert!(mean.abs() < 1e-6);
+        assert!((std_dev - (16.0f32 / 6.0).sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn frequency_offset_estimation_and_correction() {
+        let rate = SamplingRate::new(8000);
+        let nominal = Frequency::new(1000.0);
+        let mut capture = tone(1050.0, 8000, 8000);
+        let mut fft = FftPlanner::new();
+
+        let offset = estimate_frequency_offset(Samples(&capture), nominal, rate, &mut fft);
+        assert!((offset.value() - 50.0).abs() <= 1.0);
+
+        correct_frequency_offset(SamplesMut(&mut capture), offset, rate);
+        let corrected = crate::proc::peak_frequency(Samples(&capture), rate, &mut fft);
+        assert!((corrected.value() - nominal.value()).abs() <= 1.0);
+    }
+
+    #[test]
+    fn frequency_offset_of_empty_capture() {
+        let offset = estimate_frequency_offset(
+            Samples(&[]),
+            Frequency::new(1000.0),
+            SamplingRate::new(8000),
+            &mut FftPlanner::new(),
+        );
+        assert_eq!(offset, Frequency::new(0.0));
+    }
+
+    #[test]
+    fn symbol_rate_from_multiples_of_baud_period() {
+        let periods = [1, 3, 1, 2, 1, 1, 4, 2, 1, 5, 1];
+        let mut transitions = vec![250usize];
+        for p in periods.iter() {
+            transitions.push(transitions.last().unwrap() + p * 48);
+        }
+        let baudrate = symbol_rate_from_intervals(&transitions, SamplingRate::new(48000));
+        assert!((baudrate.value() - 1000.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn modulation_index_of_two_level_envelope() {
+        let envelope = [0.25f32, 0.25, 0.75, 0.75, 0.25, 0.75, 0.75, 0.25];
+        assert_eq!(modulation_index(Samples(&envelope)), Proportion::new(0.5));
+    }
+
+    #[test]
+    fn modulation_index_of_empty_envelope() {
+        assert_eq!(modulation_index(Samples(&[])), Proportion::new(0.0));
+    }
+
+    #[test]
+    fn clean_envelope_is_not_overmodulated() {
+        let envelope = [0.4f32, 0.4, 1.0, 1.0, 1.0, 0.4, 0.4, 1.0];
+        assert!(!is_overmodulated(Samples(&envelope), Amplitude::new(0.1)));
+    }
+
+    #[test]
+    fn envelope_dipping_to_zero_is_overmodulated() {
+        let envelope = [0.4f32, 0.4, 1.0, 1.0, 1.0, 0.4, 0.0, 0.4];
+        assert!(is_overmodulated(Samples(&envelope), Amplitude::new(0.1)));
+    }
+
+    #[test]
+    fn otsu_threshold_between_two_levels() {
+        let envelope: Vec<f32> = noise(400, 3)
+            .iter()
+            .enumerate()
+            .map(|(i, n)| {
+                let level = if (i / 40) % 3 == 0 { 0.9 } else { 0.2 };
+                level + 0.02 * n
+            })
+            .collect();
+        let threshold = otsu_threshold(Samples(&envelope), 64);
+        assert!(threshold > Amplitude::new(0.25));
+        assert!(threshold < Amplitude::new(0.85));
+    }
+
+    #[test]
+    fn bit_energies_of_alternating_pattern() {
+        let mut envelope = vec![0.0f32; 5];
+        for bit in 0..8 {
+            let level = if bit % 2 == 0 { 0.5 } else { -0.5 };
+            envelope.extend(std::iter::repeat_n(level, 10));
+        }
+        envelope.extend([0.5f32; 3]);
+
+        let energies = bit_energies(Samples(&envelope), 10, 5);
+        assert_eq!(energies.len(), 8);
+        for energy in energies {
+            assert!((energy - 2.5).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn noise_profile_of_lead_in() {
+        let mut capture: Vec<f32> = noise(2048, 0x1234_5678).iter().map(|n| n * 0.3).collect();
+        capture.extend(tone(1000.0, 8000, 2048));
+
+        let profile = noise_profile(
+            Samples(&capture),
+            SampleCount::from(2048),
+            &mut FftPlanner::new(),
+        );
+        assert_eq!(profile.len(), 1025);
+
+        let noise_rms = 0.3 / 3.0f32.sqrt();
+        let mean = profile.iter().map(|a| a.value()).sum::<f32>() / (profile.len() as f32);
+        // The magnitude of complex Gaussian noise is Rayleigh distributed with mean sqrt(π)/2 RMS
+        let expected = noise_rms * std::f32::consts::PI.sqrt() / 2.0;
+        assert!((mean - expected).abs() < expected * 0.1);
+        assert!(profile.iter().all(|a| a.value() < noise_rms * 4.0));
+    }
+
+    #[test]
+    fn symbol_boundaries_at_edges() {
+        let mut envelope = vec![0.0f32; 20];
+        envelope.extend((1..=10).map(|i| i as f32 / 10.0));
+        envelope.extend([1.0f32; 40]);
+        envelope.extend((1..=10).map(|i| 1.0 - i as f32 / 10.0));
+        envelope.extend([0.0f32; 20]);
+
+        let boundaries = symbol_boundaries(Samples(&envelope), Amplitude::new(0.05));
+        assert_eq!(boundaries, vec![24, 74]);
+    }
+
+    #[test]
+    fn analytic_signal_of_sine_has_constant_magnitude() {
+        let sine: Vec<f32> = tone(500.0, 8000, 1600).iter().map(|x| x * 0.7).collect();
+        let analytic = analytic_signal(Samples(&sine), &mut FftPlanner::new());
+        for (a, x) in analytic.iter().zip(sine.iter()) {
+            assert!((a.norm() - 0.7).abs() < 1e-3);
+            assert!((a.re - x).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn detrend_linearly_fading_envelope() {
+        let mut envelope: Vec<f32> = (0..1600)
+            .map(|i| {
+                let level = if (i / 50) % 2 == 0 { 1.0 } else { 0.3 };
+                level * (1.0 - 0.5 * (i as f32) / 1600.0)
+            })
+            .collect();
+
+        let (slope, intercept) = detrend_amplitude(SamplesMut(&mut envelope));
+        assert!((slope + 0.5 / 1600.0).abs() < 1e-5);
+        assert!((intercept - 1.0).abs() < 1e-2);
+
+        let (residual_slope, _) = detrend_amplitude(SamplesMut(&mut envelope));
+        assert!(residual_slope.abs() < 1e-6);
+        assert!(envelope.iter().all(|x| *x < 1.01));
+    }
+
+    #[test]
+    fn rms_follower_converges_on_sine() {
+        let rate = SamplingRate::new(8000);
+        let mut capture: Vec<f32> = tone(200.0, 8000, 1600).iter().map(|x| 0.5 * x).collect();
+        let mut follower = RmsFollower::new(Time::new(0.02), rate);
+        let (first, second) = capture.split_at_mut(700);
+        follower.process(SamplesMut(first));
+        follower.process(SamplesMut(second));
+
+        assert!(capture[80] < 0.5 / 2.0f32.sqrt() * 0.9);
+        let expected = 0.5 / 2.0f32.sqrt();
+        for level in capture[160..].iter() {
+            assert!((level - expected).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn rate_mismatch_reports_implied_rate() {
+        let carrier = Frequency::new(1000.0);
+        let mut fft = FftPlanner::new();
+        // A 1 kHz carrier recorded at 44.1 kHz, but read back as if it was 48 kHz
+        let capture = tone(1000.0, 44100, 44100);
+
+        assert!(check_rate(
+            Samples(&capture),
+            carrier,
+            SamplingRate::new(44100),
+            &mut fft
+        )
+        .is_ok());
+        let Err(RateMismatch::Implied(implied)) = check_rate(
+            Samples(&capture),
+            carrier,
+            SamplingRate::new(48000),
+            &mut fft,
+        ) else {
+            panic!("mismatch not reported");
+        };
+        assert!((implied.value() - 44100.0).abs() < 100.0);
+    }
+
+    #[test]
+    fn rate_of_capture_without_carrier() {
+        let carrier = Frequency::new(1000.0);
+        let rate = SamplingRate::new(8000);
+        let mut fft = FftPlanner::new();
+        for capture in [vec![], vec![0.0; 800], vec![0.5; 800]] {
+            assert_eq!(
+                check_rate(Samples(&capture), carrier, rate, &mut fft),
+                Err(RateMismatch::NoCarrier)
+            );
+        }
+    }
+
+    #[test]
+    fn transition_width_of_generated_frame() {
+        use crate::encodings::enc::nrz::Parameters;
+        use crate::signals::enc::am::{NRZConsts, NRZ};
+        use crate::signals::Signal;
+
+        let rate = SamplingRate::new(48000);
+        let baud = Frequency::new(1000.0);
+        for transition_width in [0.2, 0.5] {
+            let mut nrz = NRZ::new(
+                NRZConsts::new(
+                    baud,
+                    Proportion::new(transition_width),
+                    (Amplitude::new(1.0), Amplitude::new(0.2)),
+                ),
+                Parameters::new(vec![0b_1011_0010, 0b_0110_1101], 4),
+            );
+            let dt = Time::new(1.0 / 48000.0);
+            let mut envelope = vec![];
+            while let Ok(amplitude) = nrz.advance_with(dt) {
+                envelope.push(amplitude.value());
+            }
+
+            let estimate = estimate_transition_width(Samples(&envelope), rate, baud);
+            assert!((estimate.value() - transition_width).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn isolation_grows_with_channel_spacing() {
+        let rate = SamplingRate::new(16000);
+        let mut fft = FftPlanner::new();
+        let target = Frequency::new(5000.0);
+        let mut isolation = |neighbor: f32| {
+            let capture: Vec<f32> = tone(5000.0, 16000, 16000)
+                .iter()
+                .zip(tone(neighbor, 16000, 16000).iter())
+                .map(|(a, b)| a + b)
+                .collect();
+            channel_isolation(
+                Samples(&capture),
+                target,
+                Frequency::new(neighbor),
+                Frequency::new(500.0),
+                rate,
+                &mut fft,
+            )
+            .unwrap()
+        };
+
+        assert!(isolation(7000.0).value() > 30.0);
+        assert!(isolation(3000.0).value() > 30.0);
+        assert!(isolation(5300.0).value() < 6.0);
+    }
+
+    #[test]
+    fn carrier_amplitude_measured_back() {
+        let rate = SamplingRate::new(8000);
+        let mut fft = FftPlanner::new();
+        for (freq, amplitude) in [(1000.0, 0.5), (1234.5, 0.8), (777.7, 0.05)] {
+            let capture: Vec<f32> = tone(freq, 8000, 4000)
+                .iter()
+                .map(|x| amplitude * x)
+                .collect();
+            let measured =
+                measure_carrier_amplitude(Samples(&capture), Frequency::new(freq), rate, &mut fft);
+            assert!((measured.value() - amplitude).abs() < 0.01 * amplitude);
+        }
+    }
+
+    #[test]
+    fn minimum_detectable_level_over_noise_floor() {
+        let level = minimum_detectable_level(Amplitude::new(0.01), Decibel::new(6.0));
+        assert!((level.value() - 0.01 * 10.0f32.powf(6.0 / 20.0)).abs() < 1e-7);
+        assert!((level.value() - 0.02).abs() < 1e-4);
+        assert_eq!(
+            minimum_detectable_level(Amplitude::new(0.01), Decibel::new(0.0)),
+            Amplitude::new(0.01)
+        );
+    }
+
+    #[test]
+    fn calibrate_gain_and_offset_from_preamble() {
+        // Two trapezoidal pulses between 0.2 and 1.0
+        let template: Vec<f32> = (0..40)
+            .map(|i| match i % 20 {
+                0..=4 => 0.2 + 0.16 * (i % 20) as f32,
+                5..=9 => 1.0,
+                10..=14 => 1.0 - 0.16 * (i % 20 - 10) as f32,
+                _ => 0.2,
+            })
+            .collect();
+        let mut frame = template.clone();
+        frame.extend([1.0, 0.2, 0.2, 1.0]);
+        let capture: Vec<f32> = frame.iter().map(|x| 0.35 * x - 0.12).collect();
+
+        let (gain, offset) = calibrate_from_preamble(Samples(&capture), &template);
+        let calibrated: Vec<f32> = capture.iter().map(|x| gain * x + offset).collect();
+        assert!(calibrated
+            .iter()
+            .zip(frame.iter())
+            .all(|(c, f)| (c - f).abs() < 1e-5));
+    }
+
+    #[test]
+    fn extract_frame_from_padded_capture() {
+        let params = crate::encodings::enc::nrz::Parameters::new(vec![0b_1011_0010], 4);
+        // 1 start of frame, 8 payload and 6 end of frame symbols, 10 samples each
+        let mut capture = vec![0.0f32; 37];
+        capture.extend(vec![1.0f32; 150]);
+        capture.extend(vec![0.0f32; 60]);
+        let rate = SamplingRate::new(10000);
+
+        let frame = extract_frame(Samples(&capture), 37, Frequency::new(1000.0), &params, rate);
+        assert_eq!(frame.as_slice().len(), 150);
+        assert!(frame.as_slice().iter().all(|x| *x == 1.0));
+        assert!(std::ptr::eq(frame.as_slice().as_ptr(), &capture[37]));
+
+        let truncated = extract_frame(
+            Samples(&capture),
+            200,
+            Frequency::new(1000.0),
+            &params,
+            rate,
+        );
+        assert_eq!(truncated.as_slice().len(), 47);
+    }
+
+    #[test]
+    fn noise_floor_percentile_ignores_signal() {
+        let quiet: Vec<f32> = noise(4000, 23).iter().map(|n| 0.01 * n).collect();
+        // Noise only for the first half, then a strong carrier on top of it
+        let capture: Vec<f32> = noise(8000, 24)
+            .iter()
+            .zip(tone(1000.0, 8000, 8000).iter())
+            .enumerate()
+            .map(|(i, (n, carrier))| {
+                if i < 4000 {
+                    0.01 * n
+                } else {
+                    carrier + 0.01 * n
+                }
+            })
+            .collect();
+
+        let floor = noise_floor_percentile(Samples(&capture), Proportion::new(0.1));
+        let quiet_floor = noise_floor_percentile(Samples(&quiet), Proportion::new(0.2));
+        assert!(floor.value() < 0.01);
+        assert!((floor.value() - quiet_floor.value()).abs() < 0.001);
+        assert_eq!(
+            noise_floor_percentile(Samples(&[3.0, -1.0, 2.0]), Proportion::new(1.0)),
+            Amplitude::new(3.0)
+        );
+    }
+
+    #[test]
+    fn carrier_leakage_of_nonzero_low_level() {
+        let rate = SamplingRate::new(8000);
+        let mut fft = FftPlanner::new();
+        let capture: Vec<f32> = tone(1000.0, 8000, 8000)
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                if (2000..6000).contains(&i) {
+                    *x
+                } else {
+                    0.03 * x
+                }
+            })
+            .collect();
+
+        let leakage = carrier_leakage(
+            Samples(&capture),
+            Frequency::new(1000.0),
+            rate,
+            &mut fft,
+            0..2000,
+        );
+        assert!((leakage.value() - 0.03).abs() < 0.001);
+        let tail = carrier_leakage(
+            Samples(&capture),
+            Frequency::new(1000.0),
+            rate,
+            &mut fft,
+            6000..9000,
+        );
+        assert!((tail.value() - 0.03).abs() < 0.001);
+    }
+
+    #[test]
+    fn isolation_without_energy() {
+        let mut fft = FftPlanner::new();
+        for capture in [vec![], vec![0.0; 1600]] {
+            let isolation = channel_isolation(
+                Samples(&capture),
+                Frequency::new(5000.0),
+                Frequency::new(7000.0),
+                Frequency::new(500.0),
+                SamplingRate::new(16000),
+                &mut fft,
+            );
+            assert_eq!(isolation, None);
+        }
+    }
+}
@@ -1 +1,171 @@
+use rustfft::FftPlanner;
+
+use crate::sampling::{Samples, SamplingRate};
+use crate::units::{Decibel, Frequency, Proportion};
+
 pub mod am;
+
+/// Frequencies of the carriers present in a capture
+///
+/// Carriers are the spectral peaks whose power is at least `threshold` relative to the strongest
+/// one (so the threshold is normally negative). Weaker peaks closer than `min_separation` to a
+/// stronger carrier are taken to be part of it. The result is ordered by frequency.
+pub fn scan_carriers(
+    s: Samples,
+    rate: SamplingRate,
+    min_separation: Frequency,
+    threshold: Decibel,
+    fft: &mut FftPlanner<f32>,
+) -> Vec<Frequency> {
+    let len = s.as_slice().len();
+    let powers: Vec<f32> = crate::proc::windowed_spectrum(s, fft)
+        .iter()
+        .take(len / 2 + 1)
+        .map(|bin| bin.norm_sqr())
+        .collect();
+    let strongest = powers.iter().copied().fold(0.0f32, f32::max);
+    if strongest == 0.0 {
+        return Vec::new();
+    }
+    let floor = strongest * threshold.power_ratio();
+
+    let mut peaks: Vec<(usize, f32)> = powers
+        .windows(3)
+        .enumerate()
+        .filter(|(_, w)| w[1] > w[0] && w[1] >= w[2] && w[1] >= floor)
+        .map(|(idx, w)| (idx + 1, w[1]))
+        .collect();
+    peaks.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let bin_width = (rate.value() as f32) / (len as f32);
+    let mut carriers: Vec<Frequency> = Vec::new();
+    for (bin, _) in peaks {
+        let frequency = Frequency::new(bin as f32 * bin_width);
+        if carriers
+            .iter()
+            .all(|c| (c.value() - frequency.value()).abs() >= min_separation.value())
+        {
+            carriers.push(frequency);
+        }
+    }
+    carriers.sort_by(|a, b| a.value().total_cmp(&b.value()));
+    carriers
+}
+
+/// Proportion of the expected payload's bits decoded wrongly
+///
+/// Bytes missing from or in excess of the decoded payload count as eight errors each, out of the
+/// bits of the longer payload.
+pub fn bit_error_rate(decoded: &[u8], expected: &[u8]) -> Proportion {
+    let total_bits = 8 * decoded.len().max(expected.len());
+    if total_bits == 0 {
+        return Proportion::new(0.0);
+    }
+    let flipped: u32 = decoded
+        .iter()
+        .zip(expected.iter())
+        .map(|(d, e)| (d ^ e).count_ones())
+        .sum();
+    let missing = 8 * decoded.len().abs_diff(expected.len());
+    Proportion::new(((flipped as usize) + missing) as f32 / (total_bits as f32))
+}
+
+/// Envelope cut into one trace per whole baud period starting at `offset`, to be overlaid as an
+/// eye diagram
+///
+/// The wider the gap between the levels the traces pass through mid-baud, the more reliably the
+/// symbols can be told apart.
+pub fn eye_diagram(envelope: Samples, samples_per_baud: usize, offset: usize) -> Vec<Vec<f32>> {
+    if samples_per_baud == 0 {
+        return Vec::new();
+    }
+    envelope
+        .as_slice()
+        .get(offset..)
+        .unwrap_or(&[])
+        .chunks_exact(samples_per_baud)
+        .map(|trace| trace.to_vec())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::tones;
+
+    #[test]
+    fn scan_three_carriers() {
+        let rate = 16000;
+        let capture = tones(&[(1000.0, 1.0), (2500.0, 0.5), (4000.0, 0.3)], rate, rate);
+
+        let carriers = scan_carriers(
+            Samples(&capture),
+            SamplingRate::new(rate),
+            Frequency::new(200.0),
+            Decibel::new(-30.0),
+            &mut FftPlanner::new(),
+        );
+        assert_eq!(
+            carriers,
+            vec![
+                Frequency::new(1000.0),
+                Frequency::new(2500.0),
+                Frequency::new(4000.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn eye_of_clean_two_level_signal_is_open() {
+        let bits = [
+            true, false, false, true, true, false, true, false, true, true,
+        ];
+        let samples_per_baud = 16;
+        let mut envelope = vec![0.0f32; 5];
+        for bit in bits.iter() {
+            let level = if *bit { 0.9 } else { 0.1 };
+            envelope.extend(std::iter::repeat_n(level, samples_per_baud));
+        }
+
+        let traces = eye_diagram(Samples(&envelope), samples_per_baud, 5);
+        assert_eq!(traces.len(), bits.len());
+        assert!(traces.iter().all(|t| t.len() == samples_per_baud));
+        let centers: Vec<f32> = traces.iter().map(|t| t[samples_per_baud / 2]).collect();
+        let highs: Vec<f32> = centers.iter().copied().filter(|c| *c > 0.5).collect();
+        let lows: Vec<f32> = centers.iter().copied().filter(|c| *c <= 0.5).collect();
+        assert_eq!(highs.len(), 6);
+        assert_eq!(lows.len(), 4);
+        let opening = highs.iter().copied().fold(f32::MAX, f32::min)
+            - lows.iter().copied().fold(f32::MIN, f32::max);
+        assert!(opening > 0.7);
+    }
+
+    #[test]
+    fn bit_error_rate_of_payloads() {
+        let expected = [0b_1011_0010, 0b_0110_1101, 0b_0000_0000, 0b_1111_1111];
+        assert_eq!(bit_error_rate(&expected, &expected), Proportion::new(0.0));
+
+        let mut flipped = expected;
+        flipped[2] ^= 0b_0001_0000;
+        assert_eq!(
+            bit_error_rate(&flipped, &expected),
+            Proportion::new(1.0 / 32.0)
+        );
+        assert_eq!(
+            bit_error_rate(&expected[..3], &expected),
+            Proportion::new(8.0 / 32.0)
+        );
+    }
+
+    #[test]
+    fn scan_empty_capture() {
+        let carriers = scan_carriers(
+            Samples(&[]),
+            SamplingRate::new(8000),
+            Frequency::new(200.0),
+            Decibel::new(-30.0),
+            &mut FftPlanner::new(),
+        );
+        assert!(carriers.is_empty());
+    }
+}
@@ -1,14 +1,28 @@
+use rustfft::FftPlanner;
+
 use crate::encodings::{self};
+use crate::sampling::{SampleCount, Samples, SamplingRate};
 use crate::units::{Amplitude, Frequency, Proportion, Time};
+use crate::waves::{GaussianPulse, Sine};
 
-use crate::signals::{BinaryLevel, Signal};
+use crate::signals::{BinaryLevel, Signal, WaveSignal};
 
 type NRZEncoder = encodings::enc::nrz::NRZ;
 
+/// Shape of the amplitude change during a transition
+#[derive(Clone, Copy)]
+pub enum TransitionShape {
+    Linear,
+    /// Gaussian filtered edge spread over the whole baud, with the given bandwidth-time product
+    /// relative to the baud length, as in GMSK. The transition width does not apply to it.
+    Gaussian(Proportion),
+}
+
 pub struct NRZConsts {
     transition_width: Time,
     baud_length: Time,
     highlow: (Amplitude, Amplitude),
+    shape: TransitionShape,
 }
 
 impl NRZConsts {
@@ -20,24 +34,113 @@ impl NRZConsts {
         Self {
             baud_length: baudrate.cycle_time(),
             transition_width: baudrate.cycle_time() * transition_width.value(),
-            highlow: highlow,
+            highlow,
+            shape: TransitionShape::Linear,
+        }
+    }
+
+    /// Constructs the constants with the duration of a transition given directly instead of as a
+    /// proportion of the baud
+    pub fn with_absolute_transition(
+        baudrate: Frequency,
+        transition: Time,
+        highlow: (Amplitude, Amplitude),
+    ) -> Self {
+        Self {
+            baud_length: baudrate.cycle_time(),
+            transition_width: transition,
+            highlow,
+            shape: TransitionShape::Linear,
+        }
+    }
+
+    /// Duration of a transition
+    pub fn transition_time(&self) -> Time {
+        self.transition_width
+    }
+
+    pub fn with_shape(mut self, shape: TransitionShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Portion of the amplitude change done `elapsed` into a linear transition
+    fn transition_progress(&self, elapsed: Time) -> f32 {
+        (elapsed / self.transition_width).clamp(0.0, 1.0)
+    }
+
+    /// Longest time step that still resolves the transitions: the ramp of a linear transition, or
+    /// a whole baud for Gaussian edges, which spread over the surrounding bauds instead
+    fn max_step(&self) -> Time {
+        match self.shape {
+            TransitionShape::Linear => self.transition_width,
+            TransitionShape::Gaussian(_) => self.baud_length,
         }
     }
+
+    /// Number of bauds on either side of the current one whose level still affects it
+    fn reach(&self) -> usize {
+        match self.shape {
+            TransitionShape::Linear => 0,
+            TransitionShape::Gaussian(bt) => {
+                let sigma = GaussianPulse::new(bt, self.baud_length, Amplitude::new(1.0)).sigma();
+                (4.0 * (sigma / self.baud_length)).ceil() as usize + 1
+            },
+        }
+    }
+}
+
+/// Whether the symbol changes the level it starts from
+fn transitions(value: encodings::enc::nrz::Value, level: BinaryLevel) -> bool {
+    match value {
+        encodings::enc::nrz::Value::StartOfFrame
+        | encodings::enc::nrz::Value::StuffBit
+        | encodings::enc::nrz::Value::Bit(true) => true,
+        encodings::enc::nrz::Value::EndOfFrame(eofidx) => match (level, eofidx) {
+            (BinaryLevel::Low, 0) => true,
+            (BinaryLevel::Low, _) => false,
+            (BinaryLevel::High, 0) => true,
+            (BinaryLevel::High, 1) => true,
+            (BinaryLevel::High, _) => false,
+        },
+        _ => false,
+    }
 }
 
 struct NRZState {
     nrz: NRZEncoder,
     current_transition_progress: Time,
     current_level: BinaryLevel,
+    /// Levels held at the end of the bauds around the current one, which is in the middle
+    levels: std::collections::VecDeque<BinaryLevel>,
+    /// Encoder running ahead of `nrz` to provide the upcoming levels
+    ahead: NRZEncoder,
+    ahead_level: BinaryLevel,
 }
 
 impl NRZState {
-    pub fn init(nrz_params: encodings::enc::nrz::Parameters) -> Self {
-        Self {
-            nrz: NRZEncoder::new(nrz_params),
+    pub fn init(nrz_params: encodings::enc::nrz::Parameters, reach: usize) -> Self {
+        let mut state = Self {
+            nrz: NRZEncoder::new(nrz_params.clone()),
             current_transition_progress: Time::zero(),
             current_level: BinaryLevel::Low,
+            levels: std::iter::repeat_n(BinaryLevel::Low, reach).collect(),
+            ahead: NRZEncoder::new(nrz_params),
+            ahead_level: BinaryLevel::Low,
+        };
+        for _ in 0..=reach {
+            let level = state.next_level();
+            state.levels.push_back(level);
         }
+        state
+    }
+
+    fn next_level(&mut self) -> BinaryLevel {
+        if transitions(self.ahead.current(), self.ahead_level) {
+            self.ahead_level = self.ahead_level.neg();
+        }
+        self.ahead.advance();
+        self.ahead_level
     }
 }
 
@@ -48,9 +151,10 @@ pub struct NRZ {
 
 impl NRZ {
     pub fn new(c: NRZConsts, nrz_params: encodings::enc::nrz::Parameters) -> Self {
+        let reach = c.reach();
         Self {
-            c: c,
-            m: NRZState::init(nrz_params),
+            c,
+            m: NRZState::init(nrz_params, reach),
         }
     }
 
@@ -62,16 +166,41 @@ impl NRZ {
     }
 
     fn current_value(&self) -> Amplitude {
-        if !self.transition() {
+        if let TransitionShape::Gaussian(bt) = self.c.shape {
+            self.gaussian_filtered_level(bt)
+        } else if !self.transition() {
             self.level_to_amplitude(self.m.current_level)
         } else {
             self.caluclate_transition_slope()
         }
     }
 
+    /// Levels of the surrounding bauds as rectangles, convolved with the Gaussian
+    ///
+    /// Each level is held from the middle of its baud, so the edges are centered there.
+    fn gaussian_filtered_level(&self, bt: Proportion) -> Amplitude {
+        let pulse = GaussianPulse::new(bt, self.c.baud_length, Amplitude::new(1.0));
+        let reach = (self.m.levels.len() / 2) as f32;
+        self.m
+            .levels
+            .iter()
+            .enumerate()
+            .fold(Amplitude::zero(), |acc, (idx, level)| {
+                // Time since the middle of the baud holding this level
+                let since_middle = self.m.current_transition_progress
+                    - self.c.baud_length * ((idx as f32) - reach + 0.5);
+                let weight = pulse.filtered_step(since_middle).value()
+                    - pulse
+                        .filtered_step(since_middle - self.c.baud_length)
+                        .value();
+                acc + self.level_to_amplitude(*level) * weight
+            })
+    }
+
     fn caluclate_transition_slope(&self) -> Amplitude {
-        let progress =
-            (self.m.current_transition_progress / self.c.transition_width).clamp(0.0, 1.0);
+        let progress = self
+            .c
+            .transition_progress(self.m.current_transition_progress);
         let (from, to) = (
             self.level_to_amplitude(self.m.current_level),
             self.level_to_amplitude(self.m.current_level.neg()),
@@ -81,7 +210,7 @@ impl NRZ {
     }
 
     fn advance(&mut self, dt: Time) -> Result<(), crate::signals::Error> {
-        if dt > self.c.transition_width {
+        if dt > self.c.max_step() {
             return Err(crate::signals::Error::Undersampled);
         }
 
@@ -92,6 +221,9 @@ impl NRZ {
                 self.m.current_level = self.m.current_level.neg()
             }
             self.m.nrz.advance();
+            self.m.levels.pop_front();
+            let level = self.m.next_level();
+            self.m.levels.push_back(level);
         }
 
         if let encodings::enc::nrz::Value::Complete = self.m.nrz.current() {
@@ -102,21 +234,7 @@ impl NRZ {
     }
 
     fn transition(&self) -> bool {
-        match self.m.nrz.current() {
-            encodings::enc::nrz::Value::StartOfFrame
-            | encodings::enc::nrz::Value::StuffBit
-            | encodings::enc::nrz::Value::Bit(true) => true,
-            encodings::enc::nrz::Value::EndOfFrame(eofidx) => {
-                match (self.m.current_level, eofidx) {
-                    (BinaryLevel::Low, 0) => true,
-                    (BinaryLevel::Low, _) => false,
-                    (BinaryLevel::High, 0) => true,
-                    (BinaryLevel::High, 1) => true,
-                    (BinaryLevel::High, _) => false,
-                }
-            },
-            _ => false,
-        }
+        transitions(self.m.nrz.current(), self.m.current_level)
     }
 }
 
@@ -128,6 +246,114 @@ impl Signal for NRZ {
     }
 }
 
+/// Sample indices at which the transitions of the frame cross halfway between the levels when it
+/// is sampled at the rate
+///
+/// A linear transition crosses halfway through its ramp, which starts with the symbol. A Gaussian
+/// edge is centered in the middle of its symbol's baud.
+pub fn expected_edge_samples(
+    c: NRZConsts,
+    nrz_params: encodings::enc::nrz::Parameters,
+    rate: SamplingRate,
+) -> Vec<usize> {
+    let samples_per_baud = c.baud_length.value() * (rate.value() as f32);
+    let halfway = match c.shape {
+        TransitionShape::Linear => c.transition_width.value() / 2.0,
+        TransitionShape::Gaussian(_) => c.baud_length.value() / 2.0,
+    } * (rate.value() as f32);
+    let mut nrz = NRZ::new(c, nrz_params);
+    let mut edges = Vec::new();
+    let mut symbol = 0;
+    while !matches!(nrz.m.nrz.current(), encodings::enc::nrz::Value::Complete) {
+        if nrz.transition() {
+            edges.push(((symbol as f32) * samples_per_baud + halfway).round() as usize);
+            nrz.m.current_level = nrz.m.current_level.neg();
+        }
+        nrz.m.nrz.advance();
+        symbol += 1;
+    }
+    edges
+}
+
+/// Lower and upper edge of the main modulation lobe around the carrier
+///
+/// Sharper transitions spread the lobe: its width is `baudrate / transition_width`.
+pub fn expected_band(
+    carrier: Frequency,
+    baudrate: Frequency,
+    transition_width: Proportion,
+) -> (Frequency, Frequency) {
+    let half_bandwidth = baudrate.value() / transition_width.value() / 2.0;
+    (
+        Frequency::new(carrier.value() - half_bandwidth),
+        Frequency::new(carrier.value() + half_bandwidth),
+    )
+}
+
+/// Envelope of one ideal rising symbol: a linear edge from low to high over `transition` samples,
+/// then held high for the rest of the baud
+///
+/// Normalized correlation of an envelope against it peaks where a rising edge starts.
+pub fn symbol_template(
+    high_low: (Amplitude, Amplitude),
+    baud: SampleCount,
+    transition: SampleCount,
+) -> Vec<f32> {
+    let (high, low) = (high_low.0.value(), high_low.1.value());
+    (0..baud.value())
+        .map(|idx| {
+            if idx < transition.value() {
+                low + (high - low) * (idx as f32) / (transition.value() as f32)
+            } else {
+                high
+            }
+        })
+        .collect()
+}
+
+/// Bandwidth holding 99% of the energy of a whole frame modulated onto the carrier
+///
+/// Fails if the sampling rate is too low to resolve the transitions.
+pub fn measure_occupied_bandwidth(
+    c: NRZConsts,
+    nrz_params: encodings::enc::nrz::Parameters,
+    carrier: Frequency,
+    rate: SamplingRate,
+    fft: &mut FftPlanner<f32>,
+) -> Result<Frequency, crate::signals::Error> {
+    let frame = modulated_frame(c, nrz_params, carrier, rate)?;
+    Ok(crate::proc::occupied_bandwidth(
+        Samples(&frame),
+        rate,
+        Proportion::new(0.99),
+        fft,
+    ))
+}
+
+/// Samples a whole frame amplitude modulated onto the carrier
+fn modulated_frame(
+    c: NRZConsts,
+    nrz_params: encodings::enc::nrz::Parameters,
+    carrier: Frequency,
+    rate: SamplingRate,
+) -> Result<Vec<f32>, crate::signals::Error> {
+    let mut nrz = NRZ::new(c, nrz_params);
+    let mut oscillator = WaveSignal::new(Sine::new(carrier, Time::zero(), Amplitude::new(1.0)));
+    let dt = Time::new(1.0 / (rate.value() as f32));
+
+    let mut frame = Vec::new();
+    loop {
+        match nrz.advance_with(dt) {
+            Ok(envelope) => {
+                frame.push(envelope.value() * oscillator.advance_with(dt)?.value());
+            },
+            Err(crate::signals::Error::Finished) => break,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(frame)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -269,7 +495,280 @@ mod tests {
         ); // End EOF 4
         assert!(matches!(
             nrz.advance_with(Time::new(0.5)),
-            Err(super::super::Error::Finished)
+            Err(crate::signals::Error::Finished)
         ));
     }
+
+    #[test]
+    fn expected_band_straddles_carrier() {
+        let carrier = Frequency::new(10000.0);
+        let (low, high) = expected_band(carrier, Frequency::new(1000.0), Proportion::new(0.5));
+        assert_eq!(low, Frequency::new(9000.0));
+        assert_eq!(high, Frequency::new(11000.0));
+        assert_eq!(
+            carrier.value() - low.value(),
+            high.value() - carrier.value()
+        );
+    }
+
+    #[test]
+    fn expected_band_widens_with_sharper_transitions() {
+        let carrier = Frequency::new(10000.0);
+        let baudrate = Frequency::new(1000.0);
+        let (wide_low, wide_high) = expected_band(carrier, baudrate, Proportion::new(0.1));
+        let (narrow_low, narrow_high) = expected_band(carrier, baudrate, Proportion::new(0.5));
+        assert!(wide_low < narrow_low);
+        assert!(wide_high > narrow_high);
+    }
+
+    #[test]
+    fn sharper_transitions_occupy_more_bandwidth() {
+        let rate = SamplingRate::new(48000);
+        let mut fft = FftPlanner::new();
+        let mut measure = |transition_width: f32| {
+            measure_occupied_bandwidth(
+                NRZConsts::new(
+                    Frequency::new(1000.0),
+                    Proportion::new(transition_width),
+                    (Amplitude::new(1.0), Amplitude::new(0.0)),
+                ),
+                encodings::enc::nrz::Parameters::new(vec![0b_1011_0010, 0b_0110_1101], 4),
+                Frequency::new(8000.0),
+                rate,
+                &mut fft,
+            )
+            .unwrap()
+        };
+
+        let smooth = measure(0.5);
+        let sharp = measure(0.1);
+        assert!(sharp > smooth);
+    }
+
+    #[test]
+    fn gaussian_transitions_occupy_less_bandwidth() {
+        let rate = SamplingRate::new(48000);
+        let mut fft = FftPlanner::new();
+        let mut measure = |transition_width: f32, shape: TransitionShape| {
+            measure_occupied_bandwidth(
+                NRZConsts::new(
+                    Frequency::new(1000.0),
+                    Proportion::new(transition_width),
+                    (Amplitude::new(1.0), Amplitude::new(0.0)),
+                )
+                .with_shape(shape),
+                encodings::enc::nrz::Parameters::new(
+                    vec![
+                        0b_1011_0010,
+                        0b_0110_1101,
+                        0x5a,
+                        0xc3,
+                        0x96,
+                        0x3c,
+                        0xa5,
+                        0x69,
+                    ],
+                    4,
+                ),
+                Frequency::new(8000.0),
+                rate,
+                &mut fft,
+            )
+            .unwrap()
+        };
+
+        // The GMSK bandwidth-time product beats even linear edges spanning the whole baud
+        for transition_width in [0.25, 0.5, 1.0] {
+            let linear = measure(transition_width, TransitionShape::Linear);
+            let gaussian = measure(
+                transition_width,
+                TransitionShape::Gaussian(Proportion::new(0.3)),
+            );
+            assert!(gaussian < linear);
+        }
+    }
+
+    #[test]
+    fn gaussian_transitions_settle_between_levels() {
+        let highlow = (Amplitude::new(1.0), Amplitude::new(0.2));
+        let mut nrz = NRZ::new(
+            NRZConsts::new(Frequency::new(1000.0), Proportion::new(0.5), highlow)
+                .with_shape(TransitionShape::Gaussian(Proportion::new(0.5))),
+            encodings::enc::nrz::Parameters::new(vec![0b_1000_0000], 9),
+        );
+        let dt = Time::new(1.0 / 48000.0);
+        let mut envelope = Vec::new();
+        while let Ok(amplitude) = nrz.advance_with(dt) {
+            envelope.push(amplitude.value());
+        }
+
+        // Start of frame rises and the first bit falls back, each edge centered in its baud, then
+        // the zeros hold the low level until the end of frame
+        assert!(envelope
+            .iter()
+            .all(|x| *x >= 0.2 - 1e-4 && *x <= 1.0 + 1e-4));
+        assert!((envelope[24] - 0.6).abs() < 0.01);
+        assert!(envelope[48] > 0.9);
+        assert!((envelope[72] - 0.6).abs() < 0.01);
+        assert!(envelope[4 * 48..8 * 48]
+            .iter()
+            .all(|x| (x - 0.2).abs() < 1e-3));
+    }
+
+    #[test]
+    fn gaussian_frame_sampled_coarser_than_transition_width() {
+        let consts = |shape: TransitionShape| {
+            NRZConsts::new(
+                Frequency::new(1000.0),
+                Proportion::new(0.1),
+                (Amplitude::new(1.0), Amplitude::new(0.0)),
+            )
+            .with_shape(shape)
+        };
+        let params = || encodings::enc::nrz::Parameters::new(vec![0b_1011_0010], 4);
+        let gaussian = TransitionShape::Gaussian(Proportion::new(0.3));
+
+        // Four samples per baud are coarser than the ramp, but fine for Gaussian edges
+        let dt = Time::new(1.0 / 4000.0);
+        let mut nrz = NRZ::new(consts(gaussian), params());
+        let mut samples = 0;
+        let result = loop {
+            match nrz.advance_with(dt) {
+                Ok(_) => samples += 1,
+                Err(err) => break err,
+            }
+        };
+        assert!(matches!(result, crate::signals::Error::Finished));
+        assert!(samples > 4 * 8);
+
+        let mut linear = NRZ::new(consts(TransitionShape::Linear), params());
+        assert!(matches!(
+            linear.advance_with(dt),
+            Err(crate::signals::Error::Undersampled)
+        ));
+        let mut too_coarse = NRZ::new(consts(gaussian), params());
+        assert!(matches!(
+            too_coarse.advance_with(Time::new(1.0 / 500.0)),
+            Err(crate::signals::Error::Undersampled)
+        ));
+    }
+
+    #[test]
+    fn symbol_template_correlates_at_rising_edges() {
+        let highlow = (Amplitude::new(1.0), Amplitude::new(0.0));
+        let mut nrz = NRZ::new(
+            NRZConsts::new(Frequency::new(1000.0), Proportion::new(0.25), highlow),
+            encodings::enc::nrz::Parameters::new(vec![0b_1011_0010, 0b_0110_1101], 4),
+        );
+        let dt = Time::new(1.0 / 48000.0);
+        let mut envelope = Vec::new();
+        while let Ok(amplitude) = nrz.advance_with(dt) {
+            envelope.push(amplitude.value());
+        }
+
+        let template = symbol_template(highlow, SampleCount::from(48), SampleCount::from(12));
+        // Pearson correlation, so that neither level offsets nor long high runs score
+        let centered = |x: &[f32]| -> Vec<f32> {
+            let mean = x.iter().sum::<f32>() / (x.len() as f32);
+            x.iter().map(|v| v - mean).collect()
+        };
+        let norm = |x: &[f32]| x.iter().map(|v| v * v).sum::<f32>().sqrt();
+        let template = centered(&template);
+        let correlation: Vec<f32> = envelope
+            .windows(template.len())
+            .map(|w| {
+                let w = centered(w);
+                let energy = norm(&w) * norm(&template);
+                if energy > 0.0 {
+                    w.iter()
+                        .zip(template.iter())
+                        .map(|(x, t)| x * t)
+                        .sum::<f32>()
+                        / energy
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let best = correlation.iter().cloned().fold(f32::MIN, f32::max);
+
+        let rising_starts: Vec<usize> = (0..correlation.len())
+            .filter(|&idx| envelope[idx] == 0.0 && envelope[idx + 1] > 0.0)
+            .collect();
+        assert!(rising_starts.len() > 3);
+        for idx in 0..correlation.len() {
+            let nearest = *rising_starts
+                .iter()
+                .min_by_key(|r| r.abs_diff(idx))
+                .unwrap();
+            if idx == nearest {
+                assert!(correlation[idx] > 0.999 * best);
+            } else if idx.abs_diff(nearest) == 1 {
+                assert!(correlation[idx] < correlation[nearest]);
+            } else {
+                assert!(correlation[idx] < 0.99 * best);
+            }
+        }
+    }
+
+    #[test]
+    fn absolute_and_proportional_transition_agree() {
+        let highlow = (Amplitude::new(1.0), Amplitude::new(0.0));
+        let proportional = NRZConsts::new(Frequency::new(2.0), Proportion::new(0.25), highlow);
+        let absolute =
+            NRZConsts::with_absolute_transition(Frequency::new(2.0), Time::new(0.125), highlow);
+        assert_eq!(proportional.transition_time(), Time::new(0.125));
+        assert_eq!(absolute.transition_time(), proportional.transition_time());
+        assert_eq!(absolute.baud_length, proportional.baud_length);
+        assert_eq!(absolute.highlow, proportional.highlow);
+
+        let proportional = NRZConsts::new(Frequency::new(1200.0), Proportion::new(0.3), highlow);
+        let absolute = NRZConsts::with_absolute_transition(
+            Frequency::new(1200.0),
+            Time::new(0.3 / 1200.0),
+            highlow,
+        );
+        assert!(
+            (absolute.transition_time().value() - proportional.transition_time().value()).abs()
+                < 1e-9
+        );
+    }
+
+    #[test]
+    fn expected_edges_match_generated_frame() {
+        let highlow = (Amplitude::new(1.0), Amplitude::new(0.1));
+        let params = || encodings::enc::nrz::Parameters::new(vec![0b_1011_0010, 0b_0000_0101], 4);
+        let rate = SamplingRate::new(44100);
+
+        for shape in [
+            TransitionShape::Linear,
+            TransitionShape::Gaussian(Proportion::new(0.5)),
+        ] {
+            let consts = || {
+                NRZConsts::new(Frequency::new(1200.0), Proportion::new(0.3), highlow)
+                    .with_shape(shape)
+            };
+            let mut nrz = NRZ::new(consts(), params());
+            let dt = Time::new(1.0 / 44100.0);
+            let mut envelope = Vec::new();
+            while let Ok(amplitude) = nrz.advance_with(dt) {
+                envelope.push(amplitude.value());
+            }
+            // An edge crosses halfway between the samples on either side of the middle level
+            let middle = (highlow.0.value() + highlow.1.value()) / 2.0;
+            let detected: Vec<usize> = envelope
+                .windows(2)
+                .enumerate()
+                .filter(|(_, w)| (w[0] < middle) != (w[1] < middle))
+                .map(|(i, _)| i)
+                .collect();
+
+            let expected = expected_edge_samples(consts(), params(), rate);
+            assert_eq!(detected.len(), expected.len());
+            assert!(detected
+                .iter()
+                .zip(expected.iter())
+                .all(|(d, e)| d.abs_diff(*e) <= 1));
+        }
+    }
 }
@@ -1 +1,6 @@
 pub mod am;
+pub mod msk;
+pub mod ook;
+
+pub use msk::Msk;
+pub use ook::Ook;
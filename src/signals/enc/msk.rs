@@ -0,0 +1,120 @@
+use crate::units::{Amplitude, Frequency, Time};
+
+use crate::signals::{Error, Signal};
+
+/// Minimum shift keyed carrier: continuous phase FSK sending each bit at a quarter of the baudrate
+/// above (one) or below (zero) the carrier
+///
+/// The phase is accumulated across bits, so the envelope stays constant and the spectrum compact.
+pub struct Msk {
+    carrier: Frequency,
+    baud_length: Time,
+    deviation: f32,
+    amplitude: Amplitude,
+    bits: Vec<bool>,
+    bit: usize,
+    elapsed: Time,
+    phase: f32,
+}
+
+impl Msk {
+    pub fn new(
+        carrier: Frequency,
+        baudrate: Frequency,
+        amplitude: Amplitude,
+        bits: Vec<bool>,
+    ) -> Self {
+        Self {
+            carrier,
+            baud_length: baudrate.cycle_time(),
+            deviation: baudrate.value() / 4.0,
+            amplitude,
+            bits,
+            bit: 0,
+            elapsed: Time::zero(),
+            phase: 0.0,
+        }
+    }
+}
+
+impl Signal for Msk {
+    fn advance_with(&mut self, dt: Time) -> Result<Amplitude, Error> {
+        let Some(&bit) = self.bits.get(self.bit) else {
+            return Err(Error::Finished);
+        };
+        let result = self.amplitude * self.phase.sin();
+
+        let frequency = if bit {
+            self.carrier.value() + self.deviation
+        } else {
+            self.carrier.value() - self.deviation
+        };
+        self.phase = (self.phase + 2.0 * std::f32::consts::PI * frequency * dt.value())
+            % (2.0 * std::f32::consts::PI);
+
+        self.elapsed += dt;
+        if self.elapsed >= self.baud_length {
+            self.elapsed -= self.baud_length;
+            self.bit += 1;
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::{Sampleable, Samples, SamplingRate, SignalSampler};
+    use crate::testing::bits;
+    use crate::units::Proportion;
+    use rustfft::FftPlanner;
+
+    #[test]
+    fn msk_has_constant_envelope() {
+        let rate = SamplingRate::new(48000);
+        let mut msk = SignalSampler::new(Msk::new(
+            Frequency::new(6000.0),
+            Frequency::new(1000.0),
+            Amplitude::new(0.7),
+            bits(200, 3),
+        ));
+        let mut buffer = vec![0.0f32; 9600];
+        msk.sample_into_f32(buffer.as_mut_slice().into(), rate);
+
+        let envelope =
+            crate::signals::dec::am::analytic_signal(Samples(&buffer), &mut FftPlanner::new());
+        assert!(envelope[200..9400]
+            .iter()
+            .all(|a| (a.norm() - 0.7).abs() < 0.05));
+    }
+
+    #[test]
+    fn msk_is_narrower_than_discontinuous_fsk() {
+        let rate = SamplingRate::new(48000);
+        let (carrier, baudrate) = (6000.0, 1000.0);
+        let mut msk = vec![0.0f32; 9600];
+        SignalSampler::new(Msk::new(
+            Frequency::new(carrier),
+            Frequency::new(baudrate),
+            Amplitude::new(1.0),
+            bits(200, 3),
+        ))
+        .sample_into_f32(msk.as_mut_slice().into(), rate);
+
+        // Switching between two free running oscillators a whole baudrate apart
+        let bits = bits(200, 3);
+        let fsk: Vec<f32> = (0..9600)
+            .map(|i| {
+                let t = (i as f32) / 48000.0;
+                let shift = if bits[i / 48] { 0.5 } else { -0.5 };
+                (2.0 * std::f32::consts::PI * (carrier + shift * baudrate) * t).sin()
+            })
+            .collect();
+
+        let mut fft = FftPlanner::new();
+        let mut bandwidth = |s: &[f32]| {
+            crate::proc::occupied_bandwidth(Samples(s), rate, Proportion::new(0.99), &mut fft)
+        };
+        assert!(bandwidth(&msk) < bandwidth(&fsk));
+    }
+}
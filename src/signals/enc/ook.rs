@@ -0,0 +1,73 @@
+use crate::units::{Amplitude, Frequency, Time};
+use crate::waves::Sine;
+
+use crate::signals::{Error, Signal, WaveSignal};
+
+/// On-off keyed carrier following a schedule of `(duration, on)` segments
+///
+/// The carrier keeps running while keyed off, so its phase is continuous across segments.
+pub struct Ook {
+    carrier: WaveSignal<Sine>,
+    schedule: Vec<(Time, bool)>,
+    segment: usize,
+    elapsed: Time,
+}
+
+impl Ook {
+    pub fn new(carrier: Frequency, amplitude: Amplitude, schedule: Vec<(Time, bool)>) -> Self {
+        Self {
+            carrier: WaveSignal::new(Sine::new(carrier, Time::zero(), amplitude)),
+            schedule,
+            segment: 0,
+            elapsed: Time::zero(),
+        }
+    }
+}
+
+impl Signal for Ook {
+    fn advance_with(&mut self, dt: Time) -> Result<Amplitude, Error> {
+        let Some(&(_, on)) = self.schedule.get(self.segment) else {
+            return Err(Error::Finished);
+        };
+        let carrier = self.carrier.advance_with(dt)?;
+
+        self.elapsed += dt;
+        while let Some(&(duration, _)) = self.schedule.get(self.segment) {
+            if self.elapsed < duration {
+                break;
+            }
+            self.elapsed -= duration;
+            self.segment += 1;
+        }
+
+        Ok(if on { carrier } else { Amplitude::zero() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::{Sampleable, SamplingRate, SignalSampler};
+
+    #[test]
+    fn ook_keys_carrier_on_and_off() {
+        let mut ook = SignalSampler::new(Ook::new(
+            Frequency::new(1000.0),
+            Amplitude::new(0.8),
+            vec![
+                (Time::new(0.01), false),
+                (Time::new(0.01), true),
+                (Time::new(0.01), false),
+            ],
+        ));
+        let mut buffer = vec![1.0f32; 1600];
+        ook.sample_into_f32(buffer.as_mut_slice().into(), SamplingRate::new(48000));
+
+        assert!(buffer[..480].iter().all(|x| *x == 0.0));
+        let on_peak = buffer[480..960]
+            .iter()
+            .fold(0.0f32, |acc, x| acc.max(x.abs()));
+        assert!((on_peak - 0.8).abs() < 1e-2);
+        assert!(buffer[961..].iter().all(|x| *x == 0.0));
+    }
+}
@@ -0,0 +1,478 @@
+use rustfft::FftPlanner;
+
+use crate::sampling::{Samples, SamplesMut, SamplingRate};
+use crate::units::{Amplitude, Decibel, Frequency, Time};
+use crate::waves::{LinearChirp, Wave};
+
+/// Stateful processing stage applied in place to consecutive chunks of a stream
+pub trait Filter: Send {
+    fn process(&mut self, s: SamplesMut);
+    /// Forgets the history carried over from previous chunks
+    fn reset(&mut self);
+}
+
+/// Averages each sample with the preceding `window - 1` ones
+///
+/// The history starts out as zeros, so the first `window - 1` outputs are still rising.
+pub struct MovingAverage {
+    history: std::collections::VecDeque<f32>,
+    window: usize,
+    sum: f32,
+}
+
+impl MovingAverage {
+    /// A moving average over `window` samples, `None` for an empty window
+    pub fn new(window: usize) -> Option<Self> {
+        if window == 0 {
+            return None;
+        }
+        Some(Self {
+            history: std::collections::VecDeque::from(vec![0.0; window]),
+            window,
+            sum: 0.0,
+        })
+    }
+}
+
+impl Filter for MovingAverage {
+    fn process(&mut self, mut s: SamplesMut) {
+        for sample in s.as_mut_slice().iter_mut() {
+            self.sum -= self.history.pop_front().unwrap_or(0.0);
+            self.history.push_back(*sample);
+            self.sum += *sample;
+            *sample = self.sum / (self.window as f32);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.history.iter_mut().for_each(|x| *x = 0.0);
+        self.sum = 0.0;
+    }
+}
+
+/// Multipath channel summing delayed and scaled copies of the input, given as `(delay in samples,
+/// gain)` taps
+pub struct ChannelModel {
+    taps: Vec<(usize, f32)>,
+    history: std::collections::VecDeque<f32>,
+}
+
+impl ChannelModel {
+    pub fn new(taps: Vec<(usize, f32)>) -> Self {
+        let longest = taps.iter().map(|(delay, _)| *delay).max().unwrap_or(0);
+        Self {
+            taps,
+            history: std::collections::VecDeque::from(vec![0.0; longest + 1]),
+        }
+    }
+}
+
+impl Filter for ChannelModel {
+    fn process(&mut self, mut s: SamplesMut) {
+        for sample in s.as_mut_slice().iter_mut() {
+            self.history.pop_back();
+            self.history.push_front(*sample);
+            *sample = self
+                .taps
+                .iter()
+                .map(|(delay, gain)| self.history[*delay] * gain)
+                .sum();
+        }
+    }
+
+    fn reset(&mut self) {
+        self.history.iter_mut().for_each(|x| *x = 0.0);
+    }
+}
+
+/// Feedforward comb notching the fundamental, its harmonics and DC
+///
+/// Each output is half the difference of the input and the input one fundamental period earlier,
+/// so frequencies halfway between the notches pass at full amplitude.
+pub struct CombFilter {
+    history: std::collections::VecDeque<f32>,
+}
+
+impl CombFilter {
+    pub fn new(fundamental: Frequency, rate: SamplingRate) -> Self {
+        let delay = ((rate.value() as f32) / fundamental.value())
+            .round()
+            .max(1.0) as usize;
+        Self {
+            history: std::collections::VecDeque::from(vec![0.0; delay]),
+        }
+    }
+}
+
+impl Filter for CombFilter {
+    fn process(&mut self, mut s: SamplesMut) {
+        for sample in s.as_mut_slice().iter_mut() {
+            let delayed = self.history.pop_front().unwrap_or(0.0);
+            self.history.push_back(*sample);
+            *sample = 0.5 * (*sample - delayed);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.history.iter_mut().for_each(|x| *x = 0.0);
+    }
+}
+
+/// Output of the freshly reset filter for a unit impulse of `len` samples
+pub fn impulse_response<F: Filter>(filter: &mut F, len: usize) -> Vec<f32> {
+    let mut buffer = vec![0.0; len];
+    if let Some(first) = buffer.first_mut() {
+        *first = 1.0;
+    }
+    filter.reset();
+    filter.process(SamplesMut(&mut buffer));
+    buffer
+}
+
+/// Output of the freshly reset filter for a unit step of `len` samples
+pub fn step_response<F: Filter>(filter: &mut F, len: usize) -> Vec<f32> {
+    let mut buffer = vec![1.0; len];
+    filter.reset();
+    filter.process(SamplesMut(&mut buffer));
+    buffer
+}
+
+/// Empirical magnitude response of the filter between `f_start` and `f_end`, at bins at most
+/// `resolution` apart
+///
+/// A linear chirp covering the range is run through the freshly reset filter, and the response is
+/// the ratio of the output and input spectra at each bin of the swept range. The chirp lasts as
+/// many samples as the transform needs for the resolution. Bins where the chirp's spectrum is 40
+/// dB below its strongest bin carry too little of it for a ratio and are left out.
+pub fn measure_response<F: Filter>(
+    filter: &mut F,
+    f_start: Frequency,
+    f_end: Frequency,
+    resolution: Frequency,
+    rate: SamplingRate,
+    fft: &mut FftPlanner<f32>,
+) -> Vec<(Frequency, Amplitude)> {
+    let len = crate::proc::fft_len_for_resolution(resolution, rate);
+    let increment = Time::new(1.0 / (rate.value() as f32));
+    let chirp = LinearChirp::new(
+        f_start,
+        f_end,
+        increment * (len as f32),
+        Amplitude::new(1.0),
+    );
+    let input: Vec<f32> = (0..len)
+        .map(|idx| chirp.value_at(increment * (idx as f32)).value())
+        .collect();
+
+    let mut output = input.clone();
+    filter.reset();
+    filter.process(SamplesMut(&mut output));
+
+    let input_bins = crate::proc::spectrum(Samples(&input), fft);
+    let output_bins = crate::proc::spectrum(Samples(&output), fft);
+    let strongest = input_bins
+        .iter()
+        .take(len / 2 + 1)
+        .map(|x| x.norm())
+        .fold(0.0f32, f32::max);
+    let bin_width = crate::proc::resolution_for_len(len, rate).value();
+    input_bins
+        .iter()
+        .zip(output_bins.iter())
+        .enumerate()
+        .take(len / 2 + 1)
+        .map(|(bin, (x, y))| (Frequency::new(bin as f32 * bin_width), x, y))
+        .filter(|(f, x, _)| *f >= f_start && *f <= f_end && x.norm() > 0.01 * strongest)
+        .map(|(f, x, y)| (f, Amplitude::new(y.norm() / x.norm())))
+        .collect()
+}
+
+/// Improvement of the in-band to out-of-band energy ratio around the carrier when the capture is
+/// run through the freshly reset filter
+///
+/// The band is `bandwidth` wide and centered on the carrier; the energies are taken from the Hann
+/// windowed spectra before and after filtering. If either band is without energy before or after
+/// filtering, as for an empty or silent capture or a pure carrier, there is no SNR to compare and
+/// `None` is returned.
+pub fn snr_gain<F: Filter>(
+    filter: &mut F,
+    s: Samples,
+    carrier: Frequency,
+    bandwidth: Frequency,
+    rate: SamplingRate,
+    fft: &mut FftPlanner<f32>,
+) -> Option<Decibel> {
+    let len = s.as_slice().len();
+    let low = (carrier.value() - bandwidth.value() / 2.0).max(0.0);
+    let high = carrier.value() + bandwidth.value() / 2.0;
+    let mut snr = |samples: Samples| {
+        let (mut signal, mut noise) = (0.0f32, 0.0f32);
+        for (bin, x) in crate::proc::windowed_spectrum(samples, fft)
+            .iter()
+            .enumerate()
+            .take(len / 2 + 1)
+        {
+            let frequency = (bin * rate.value()) as f32 / (len as f32);
+            if frequency >= low && frequency <= high {
+                signal += x.norm_sqr();
+            } else {
+                noise += x.norm_sqr();
+            }
+        }
+        if signal == 0.0 || noise == 0.0 {
+            return None;
+        }
+        Some(Decibel::from_power_ratio(signal / noise))
+    };
+
+    let before = snr(s)?;
+    let mut filtered = s.as_slice().to_vec();
+    filter.reset();
+    filter.process(SamplesMut(&mut filtered));
+    let after = snr(Samples(&filtered))?;
+    Some(Decibel::new(after.value() - before.value()))
+}
+
+/// Feedback coefficient of the single pole lowpass with the given time constant
+fn pole(tau: Time, rate: SamplingRate) -> f32 {
+    (-1.0f32 / (tau.value() * (rate.value() as f32))).exp()
+}
+
+/// Boosts high frequencies in place with the first order high-shelf that exactly inverts
+/// [`deemphasis`] for the same time constant, keeping unity gain at DC
+pub fn preemphasis(mut s: SamplesMut, tau: Time, rate: SamplingRate) {
+    let a = pole(tau, rate);
+    let mut previous = 0.0f32;
+    for sample in s.as_mut_slice().iter_mut() {
+        let input = *sample;
+        *sample = (input - a * previous) / (1.0 - a);
+        previous = input;
+    }
+}
+
+/// Attenuates high frequencies in place with a single pole lowpass of the given time constant
+pub fn deemphasis(mut s: SamplesMut, tau: Time, rate: SamplingRate) {
+    let a = pole(tau, rate);
+    let mut previous = 0.0f32;
+    for sample in s.as_mut_slice().iter_mut() {
+        previous = a * previous + (1.0 - a) * *sample;
+        *sample = previous;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{noise, tone, tones};
+
+    #[test]
+    fn deemphasis_inverts_preemphasis() {
+        let original = noise(1000, 0x2545_f491);
+
+        let (tau, rate) = (Time::new(75e-6), SamplingRate::new(48000));
+        let mut buffer = original.clone();
+        preemphasis(SamplesMut(&mut buffer), tau, rate);
+        deemphasis(SamplesMut(&mut buffer), tau, rate);
+
+        for (restored, original) in buffer.iter().zip(original.iter()) {
+            assert!((restored - original).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn moving_average_response() {
+        let rate = SamplingRate::new(8000);
+        let response = measure_response(
+            &mut MovingAverage::new(8).unwrap(),
+            Frequency::new(50.0),
+            Frequency::new(3500.0),
+            Frequency::new(1.0),
+            rate,
+            &mut FftPlanner::new(),
+        );
+
+        let passband = response.iter().filter(|(f, _)| f.value() < 150.0);
+        assert!(passband.clone().count() > 50);
+        for (_, gain) in passband {
+            assert!(gain.value() > 0.9);
+        }
+
+        let stopband: Vec<f32> = response
+            .iter()
+            .filter(|(f, _)| f.value() > 1000.0)
+            .map(|(_, gain)| gain.value())
+            .collect();
+        assert!(stopband.iter().all(|gain| *gain < 0.3));
+        assert!(stopband.iter().sum::<f32>() / (stopband.len() as f32) < 0.15);
+    }
+
+    #[test]
+    fn response_over_whole_band_is_finite() {
+        let rate = SamplingRate::new(8000);
+        let mut fft = FftPlanner::new();
+        let mut measure = |resolution: f32| {
+            measure_response(
+                &mut CombFilter::new(Frequency::new(500.0), rate),
+                Frequency::new(0.0),
+                Frequency::new(4000.0),
+                Frequency::new(resolution),
+                rate,
+                &mut fft,
+            )
+        };
+
+        let fine = measure(2.0);
+        let coarse = measure(16.0);
+        assert!(fine.len() > 4 * coarse.len());
+        assert!(coarse.len() > 100);
+        for (f, gain) in fine.iter().chain(coarse.iter()) {
+            assert!(f.value() >= 0.0 && f.value() <= 4000.0);
+            assert!(gain.value().is_finite());
+        }
+
+        // A sweep that never leaves DC is silent, so no bin has energy to compare
+        let silent = measure_response(
+            &mut MovingAverage::new(8).unwrap(),
+            Frequency::new(0.0),
+            Frequency::new(0.0),
+            Frequency::new(16.0),
+            rate,
+            &mut fft,
+        );
+        assert!(silent.is_empty());
+    }
+
+    #[test]
+    fn moving_average_impulse_response() {
+        let response = impulse_response(&mut MovingAverage::new(4).unwrap(), 6);
+        assert_eq!(response, vec![0.25, 0.25, 0.25, 0.25, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn moving_average_rejects_empty_window() {
+        assert!(MovingAverage::new(0).is_none());
+    }
+
+    #[test]
+    fn moving_average_step_response_ramps_to_unity() {
+        let mut filter = MovingAverage::new(4).unwrap();
+        filter.process(SamplesMut(&mut [5.0; 3]));
+        let response = step_response(&mut filter, 6);
+        assert_eq!(response, vec![0.25, 0.5, 0.75, 1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn channel_model_impulse_response() {
+        let mut channel = ChannelModel::new(vec![(0, 1.0), (2, 0.5), (3, -0.25)]);
+        assert_eq!(
+            impulse_response(&mut channel, 5),
+            vec![1.0, 0.0, 0.5, -0.25, 0.0]
+        );
+    }
+
+    #[test]
+    fn channel_model_intersymbol_interference() {
+        let bits = [
+            true, false, true, true, false, false, true, false, true, true,
+        ];
+        let samples_per_bit = 20;
+        let levels: Vec<f32> = bits
+            .iter()
+            .flat_map(|bit| std::iter::repeat_n(if *bit { 1.0 } else { 0.0 }, samples_per_bit))
+            .collect();
+        let slice = |s: &[f32]| -> Vec<bool> {
+            s.chunks(samples_per_bit)
+                .map(|bit| bit[samples_per_bit / 2] > 0.5)
+                .collect()
+        };
+
+        // Chunked processing keeps the channel's memory
+        let mut mild = levels.clone();
+        let mut channel = ChannelModel::new(vec![(0, 0.9), (3, 0.2)]);
+        let (first, second) = mild.split_at_mut(55);
+        channel.process(SamplesMut(first));
+        channel.process(SamplesMut(second));
+        assert_eq!(slice(&mild), bits);
+
+        let mut aggressive = levels.clone();
+        ChannelModel::new(vec![(0, 0.6), (samples_per_bit, 0.8)])
+            .process(SamplesMut(&mut aggressive));
+        assert_ne!(slice(&aggressive), bits);
+    }
+
+    #[test]
+    fn comb_filter_removes_harmonic_interferer() {
+        let rate = SamplingRate::new(8000);
+        let wanted = tones(&[(1050.0, 0.5)], 8000, 8000);
+        let interferer = tones(&[(100.0, 0.3), (300.0, 0.2), (1100.0, 0.1)], 8000, 8000);
+
+        let mut filtered: Vec<f32> = wanted
+            .iter()
+            .zip(interferer.iter())
+            .map(|(w, i)| w + i)
+            .collect();
+        let mut comb = CombFilter::new(Frequency::new(100.0), rate);
+        let (first, second) = filtered.split_at_mut(1234);
+        comb.process(SamplesMut(first));
+        comb.process(SamplesMut(second));
+
+        // Past the first period the interferer is gone and the wanted tone is untouched
+        let error: Vec<f32> = filtered[80..]
+            .iter()
+            .zip(wanted[80..].iter())
+            .map(|(f, w)| f - w)
+            .collect();
+        assert!(crate::sampling::peak(Samples(&error)).value() < 1e-3);
+    }
+
+    #[test]
+    fn snr_gain_of_filtering_noisy_carrier() {
+        let rate = SamplingRate::new(8000);
+        let capture: Vec<f32> = tone(200.0, 8000, 8000)
+            .iter()
+            .zip(noise(8000, 19).iter())
+            .map(|(t, n)| t + 0.5 * n)
+            .collect();
+        let mut fft = FftPlanner::new();
+        let (carrier, bandwidth) = (Frequency::new(200.0), Frequency::new(100.0));
+
+        // Lowpass keeping the carrier
+        let lowpass = snr_gain(
+            &mut MovingAverage::new(16).unwrap(),
+            Samples(&capture),
+            carrier,
+            bandwidth,
+            rate,
+            &mut fft,
+        )
+        .unwrap();
+        assert!(lowpass.value() > 6.0);
+        // Comb notching the carrier itself
+        let notch = snr_gain(
+            &mut CombFilter::new(carrier, rate),
+            Samples(&capture),
+            carrier,
+            bandwidth,
+            rate,
+            &mut fft,
+        )
+        .unwrap();
+        assert!(notch.value() < -10.0);
+    }
+
+    #[test]
+    fn snr_gain_without_energy() {
+        let mut fft = FftPlanner::new();
+        for capture in [vec![], vec![0.0; 800]] {
+            let gain = snr_gain(
+                &mut MovingAverage::new(4).unwrap(),
+                Samples(&capture),
+                Frequency::new(200.0),
+                Frequency::new(100.0),
+                SamplingRate::new(8000),
+                &mut fft,
+            );
+            assert_eq!(gain, None);
+        }
+    }
+}
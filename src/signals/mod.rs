@@ -1,8 +1,10 @@
 use crate::units::{Amplitude, Time};
+use crate::waves::Wave;
 
 pub mod dec;
 /// Amplitude modulated signals
 pub mod enc;
+pub mod filters;
 
 #[derive(Debug)]
 pub enum Error {
@@ -14,6 +16,54 @@ pub trait Signal: Sized + Send {
     fn advance_with(&mut self, dt: Time) -> Result<Amplitude, Error>;
 }
 
+/// Signal that plays back a wave, advancing its phase by each step
+///
+/// Runs of equal steps are evaluated as `step * count` from the last shift instead of shifting the
+/// wave every time, so rounding does not accumulate. The elapsed time is folded into the wave when
+/// the step changes or the run gets long.
+pub struct WaveSignal<W: Wave> {
+    wave: W,
+    step: Time,
+    steps: usize,
+}
+
+impl<W: Wave> WaveSignal<W> {
+    const MAX_STEPS: usize = 4096;
+
+    pub fn new(w: W) -> Self {
+        Self {
+            wave: w,
+            step: Time::zero(),
+            steps: 0,
+        }
+    }
+
+    fn fold(&mut self) {
+        self.wave.shift_mut(self.step * (self.steps as f32));
+        self.steps = 0;
+    }
+}
+
+impl<W: Wave> Signal for WaveSignal<W> {
+    fn advance_with(&mut self, dt: Time) -> Result<Amplitude, Error> {
+        if dt != self.step {
+            self.fold();
+            self.step = dt;
+        }
+        let result = self.wave.value_at(self.step * (self.steps as f32));
+        self.steps += 1;
+        if self.steps == Self::MAX_STEPS {
+            self.fold();
+        }
+        Ok(result)
+    }
+}
+
+/// Explicitly turns a wave into a signal
+pub fn from_wave<W: Wave>(w: W) -> WaveSignal<W> {
+    WaveSignal::new(w)
+}
+
 #[derive(Clone, Copy)]
 enum BinaryLevel {
     Low,
@@ -48,7 +98,7 @@ where
     pub fn new(s1: S1, s2: S2, compositor: F) -> Self {
         Self {
             s: (s1, s2),
-            compositor: compositor,
+            compositor,
         }
     }
 }
@@ -64,3 +114,105 @@ where
         Ok((self.compositor)(a, dt))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::{Sampleable, SamplingRate, SignalSampler, WaveSampler};
+    use crate::units::Frequency;
+    use crate::waves::Sine;
+
+    #[test]
+    fn wave_signal_matches_wave_sampler() {
+        let rate = SamplingRate::new(48000);
+        let sine = || Sine::new(Frequency::new(440.0), Time::zero(), Amplitude::new(0.7));
+
+        let mut through_wave = vec![0.0f32; 480];
+        WaveSampler::new(sine()).sample_into_f32(through_wave.as_mut_slice().into(), rate);
+        let mut through_signal = vec![0.0f32; 480];
+        SignalSampler::new(from_wave(sine()))
+            .sample_into_f32(through_signal.as_mut_slice().into(), rate);
+
+        assert_eq!(through_wave, through_signal);
+    }
+
+    /// A wave with its own, deliberately different, signal behavior
+    struct Ramp(Time);
+
+    impl Wave for Ramp {
+        fn shift_mut(&mut self, offset: Time) {
+            self.0 += offset;
+        }
+        fn value_at(&self, t: Time) -> Amplitude {
+            Amplitude::new((self.0 + t).value())
+        }
+    }
+
+    impl Signal for Ramp {
+        fn advance_with(&mut self, _dt: Time) -> Result<Amplitude, Error> {
+            Err(Error::Finished)
+        }
+    }
+
+    #[test]
+    fn wave_may_implement_signal_itself() {
+        let mut ramp = Ramp(Time::zero());
+        assert!(matches!(
+            ramp.advance_with(Time::new(1.0)),
+            Err(Error::Finished)
+        ));
+
+        let mut wrapped = from_wave(Ramp(Time::zero()));
+        assert_eq!(
+            wrapped.advance_with(Time::new(1.0)).unwrap(),
+            Amplitude::new(0.0)
+        );
+        assert_eq!(
+            wrapped.advance_with(Time::new(1.0)).unwrap(),
+            Amplitude::new(1.0)
+        );
+    }
+
+    #[test]
+    fn composite_of_wave_signals() {
+        let mut composite = CompositeSignal::new(
+            from_wave(Ramp(Time::zero())),
+            from_wave(Ramp(Time::new(10.0))),
+            |(a, b), _| a + b,
+        );
+        assert_eq!(
+            composite.advance_with(Time::new(0.5)).unwrap(),
+            Amplitude::new(10.0)
+        );
+        assert_eq!(
+            composite.advance_with(Time::new(0.5)).unwrap(),
+            Amplitude::new(11.0)
+        );
+    }
+
+    #[test]
+    fn both_samplers_follow_the_sine_across_chunks() {
+        let rate = SamplingRate::new(44100);
+        let sine = || Sine::new(Frequency::new(1234.5), Time::new(1e-4), Amplitude::new(0.9));
+        let reference = sine();
+        let mut wave_sampler = WaveSampler::new(sine());
+        let mut signal_sampler = SignalSampler::new(from_wave(sine()));
+
+        // Chunks straddle the 4096 step fold of WaveSignal several times
+        let mut n = 0usize;
+        for chunk_len in [1, 100, 4095, 4096, 10000, 7] {
+            let mut through_wave = vec![0.0f32; chunk_len];
+            wave_sampler.sample_into_f32(through_wave.as_mut_slice().into(), rate);
+            let mut through_signal = vec![0.0f32; chunk_len];
+            signal_sampler.sample_into_f32(through_signal.as_mut_slice().into(), rate);
+
+            for (w, s) in through_wave.iter().zip(through_signal.iter()) {
+                let t = Time::new(n as f32 / rate.value() as f32);
+                let expected = reference.value_at(t).value();
+                assert!((w - expected).abs() < 1e-3, "wave sampler at {}", n);
+                assert!((s - expected).abs() < 1e-3, "signal sampler at {}", n);
+                n += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,39 @@
+pub use crate::encodings::test_payload as payload;
+
+/// `len` samples of uniform white noise on [-1, 1), the same for the same seed
+pub fn noise(len: usize, seed: u64) -> Vec<f32> {
+    payload(len * 3, seed)
+        .chunks_exact(3)
+        .map(|b| {
+            let bits = u32::from_le_bytes([b[0], b[1], b[2], 0]);
+            bits as f32 / (1u32 << 24) as f32 * 2.0 - 1.0
+        })
+        .collect()
+}
+
+/// `len` pseudo-random bits, the same for the same seed
+pub fn bits(len: usize, seed: u64) -> Vec<bool> {
+    payload(len.div_ceil(8), seed)
+        .iter()
+        .flat_map(|byte| (0..8).map(move |offset| byte & (0b1_u8 << (7 - offset)) != 0))
+        .take(len)
+        .collect()
+}
+
+/// `len` samples of a unit amplitude sine of `freq` starting at zero phase
+pub fn tone(freq: f32, rate: usize, len: usize) -> Vec<f32> {
+    tones(&[(freq, 1.0)], rate, len)
+}
+
+/// `len` samples of the sum of sines given as `(frequency, amplitude)`, all starting at zero phase
+pub fn tones(components: &[(f32, f32)], rate: usize, len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|i| {
+            let t = (i as f32) / (rate as f32);
+            components
+                .iter()
+                .map(|(freq, amplitude)| amplitude * (2.0 * std::f32::consts::PI * freq * t).sin())
+                .sum()
+        })
+        .collect()
+}
@@ -97,6 +97,10 @@ impl Amplitude {
     pub fn zero() -> Self {
         Self(0.0)
     }
+    /// Full scale 16 bit PCM value, clipping outside of `[-1.0, 1.0]`
+    pub fn to_i16(self) -> i16 {
+        (self.0.clamp(-1.0, 1.0) * (i16::MAX as f32)).round() as i16
+    }
 }
 
 impl std::ops::Add for Amplitude {
@@ -134,6 +138,32 @@ impl Proportion {
     }
 }
 
+/// Logarithmic power ratio
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+pub struct Decibel(f32);
+
+impl Decibel {
+    pub fn new(value: f32) -> Self {
+        Self(value)
+    }
+    pub fn value(self) -> f32 {
+        self.0
+    }
+    pub fn from_power_ratio(ratio: f32) -> Self {
+        Self(10.0 * ratio.log10())
+    }
+    pub fn from_amplitude_ratio(ratio: f32) -> Self {
+        Self(20.0 * ratio.log10())
+    }
+    pub fn power_ratio(self) -> f32 {
+        10.0f32.powf(self.0 / 10.0)
+    }
+    pub fn amplitude_ratio(self) -> f32 {
+        10.0f32.powf(self.0 / 20.0)
+    }
+}
+
+#[allow(dead_code)]
 trait Clampable<T> {
     fn clamp(self, lower: T, higher: T) -> T;
 }
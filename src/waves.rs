@@ -1,4 +1,5 @@
-use crate::units::{Amplitude, Frequency, Time};
+use crate::sampling::{Samples, SamplingRate};
+use crate::units::{Amplitude, Frequency, Proportion, Time};
 
 pub trait Wave: Sized + Send {
     fn shift_mut(&mut self, offset: Time);
@@ -19,19 +20,23 @@ pub struct Sine {
 impl Sine {
     pub fn new(freq: Frequency, phase_offset: Time, amplitude: Amplitude) -> Self {
         Self {
-            freq: freq,
-            phase_offset: phase_offset,
-            amplitude: amplitude,
+            freq,
+            phase_offset,
+            amplitude,
         }
     }
 }
 
+fn shifted_phase_offset(phase_offset: Time, offset: Time, freq: Frequency) -> Time {
+    let new_phase_offset_base = phase_offset + offset;
+    let cycle_time = freq.cycle_time();
+    let whole_phases = (new_phase_offset_base / cycle_time).floor();
+    new_phase_offset_base - (cycle_time * whole_phases)
+}
+
 impl Wave for Sine {
     fn shift_mut(&mut self, offset: Time) {
-        let new_phase_offset_base = self.phase_offset + offset;
-        let cycle_time = self.freq.cycle_time();
-        let whole_phases = (new_phase_offset_base / cycle_time).floor();
-        self.phase_offset = new_phase_offset_base - (cycle_time * whole_phases);
+        self.phase_offset = shifted_phase_offset(self.phase_offset, offset, self.freq);
     }
 
     fn value_at(&self, t: Time) -> Amplitude {
@@ -42,10 +47,344 @@ impl Wave for Sine {
     }
 }
 
-impl<T: Wave> crate::signals::Signal for T {
-    fn advance_with(&mut self, dt: Time) -> Result<Amplitude, crate::signals::Error> {
-        let result = self.value_at(Time::zero());
-        self.shift_mut(dt);
-        Ok(result)
+/// Number of harmonics of `freq` strictly below the Nyquist frequency of `rate`
+fn harmonics_below_nyquist(freq: Frequency, rate: SamplingRate) -> usize {
+    let nyquist = (rate.value() as f32) / 2.0;
+    ((nyquist / freq.value()).ceil() as usize).saturating_sub(1)
+}
+
+/// Sums `amplitude * weight(k) * sin(2πkft)` over the harmonics `k` in `1..=harmonics`
+fn additive_value(
+    freq: Frequency,
+    t: Time,
+    harmonics: usize,
+    weight: impl Fn(usize) -> f32,
+) -> f32 {
+    let fundamental_phase = 2.0f32 * std::f32::consts::PI * (t * freq);
+    (1..=harmonics)
+        .map(|k| weight(k) * ((k as f32) * fundamental_phase).sin())
+        .sum()
+}
+
+/// Square wave synthesized from its harmonics below the Nyquist frequency of the sampling rate it
+/// is meant for, so sampling it does not alias
+pub struct BlitSquare {
+    freq: Frequency,
+    phase_offset: Time,
+    amplitude: Amplitude,
+    harmonics: usize,
+}
+
+impl BlitSquare {
+    pub fn new(
+        freq: Frequency,
+        phase_offset: Time,
+        amplitude: Amplitude,
+        rate: SamplingRate,
+    ) -> Self {
+        Self {
+            freq,
+            phase_offset,
+            amplitude,
+            harmonics: harmonics_below_nyquist(freq, rate),
+        }
+    }
+}
+
+impl Wave for BlitSquare {
+    fn shift_mut(&mut self, offset: Time) {
+        self.phase_offset = shifted_phase_offset(self.phase_offset, offset, self.freq);
+    }
+
+    fn value_at(&self, t: Time) -> Amplitude {
+        let value = additive_value(self.freq, self.phase_offset + t, self.harmonics, |k| {
+            if k % 2 == 1 {
+                4.0 / (std::f32::consts::PI * (k as f32))
+            } else {
+                0.0
+            }
+        });
+        Amplitude::new(value * self.amplitude.value())
+    }
+}
+
+/// Rising sawtooth wave synthesized from its harmonics below the Nyquist frequency of the sampling
+/// rate it is meant for, so sampling it does not alias
+pub struct BlitSawtooth {
+    freq: Frequency,
+    phase_offset: Time,
+    amplitude: Amplitude,
+    harmonics: usize,
+}
+
+impl BlitSawtooth {
+    pub fn new(
+        freq: Frequency,
+        phase_offset: Time,
+        amplitude: Amplitude,
+        rate: SamplingRate,
+    ) -> Self {
+        Self {
+            freq,
+            phase_offset,
+            amplitude,
+            harmonics: harmonics_below_nyquist(freq, rate),
+        }
+    }
+}
+
+impl Wave for BlitSawtooth {
+    fn shift_mut(&mut self, offset: Time) {
+        self.phase_offset = shifted_phase_offset(self.phase_offset, offset, self.freq);
+    }
+
+    fn value_at(&self, t: Time) -> Amplitude {
+        let value = additive_value(self.freq, self.phase_offset + t, self.harmonics, |k| {
+            let sign = if k % 2 == 1 { 1.0 } else { -1.0 };
+            sign * 2.0 / (std::f32::consts::PI * (k as f32))
+        });
+        Amplitude::new(value * self.amplitude.value())
+    }
+}
+
+/// Train of Gaussian pulses, one centered in every baud, as used to shape GMSK transitions
+///
+/// The width of the pulses is set by the bandwidth-time product `bt` of the Gaussian filter they
+/// are the impulse response of.
+pub struct GaussianPulse {
+    bt: Proportion,
+    baud_length: Time,
+    offset: Time,
+    amplitude: Amplitude,
+}
+
+impl GaussianPulse {
+    pub fn new(bt: Proportion, baud_length: Time, amplitude: Amplitude) -> Self {
+        Self {
+            bt,
+            baud_length,
+            offset: Time::zero(),
+            amplitude,
+        }
+    }
+
+    pub(crate) fn sigma(&self) -> Time {
+        self.baud_length * (2.0f32.ln().sqrt() / (2.0 * std::f32::consts::PI * self.bt.value()))
+    }
+
+    /// Time from the center of the pulse in the baud containing `t`
+    fn distance_to_center(&self, t: Time) -> Time {
+        let t = self.offset + t;
+        let in_baud = t - self.baud_length * (t / self.baud_length).floor();
+        in_baud - self.baud_length * 0.5f32
+    }
+
+    /// Fraction of the area of the pulse accumulated by `t` into its baud, which is the Gaussian
+    /// filtered step
+    pub fn step_at(&self, t: Time) -> Proportion {
+        self.filtered_step(self.distance_to_center(t))
+    }
+
+    /// Gaussian filtered unit step `t` after the unfiltered step, not confined to a baud
+    pub(crate) fn filtered_step(&self, t: Time) -> Proportion {
+        let x = t / self.sigma() / std::f32::consts::SQRT_2;
+        Proportion::new(0.5 * (1.0 + erf(x)))
+    }
+}
+
+impl Wave for GaussianPulse {
+    fn shift_mut(&mut self, offset: Time) {
+        let new_offset = self.offset + offset;
+        let whole_bauds = (new_offset / self.baud_length).floor();
+        self.offset = new_offset - (self.baud_length * whole_bauds);
+    }
+
+    fn value_at(&self, t: Time) -> Amplitude {
+        let x = self.distance_to_center(t) / self.sigma();
+        Amplitude::new((-x * x / 2.0).exp() * self.amplitude.value())
+    }
+}
+
+/// Error function, by the Abramowitz-Stegun 7.1.26 approximation
+fn erf(x: f32) -> f32 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let polynomial = t
+        * (0.254_829_6
+            + t * (-0.284_496_74 + t * (1.421_413_7 + t * (-1.453_152 + t * 1.061_405_4))));
+    (1.0 - polynomial * (-x * x).exp()).copysign(x)
+}
+
+/// Sine whose frequency sweeps linearly from `start` to `end` over `duration`, then starts over
+pub struct LinearChirp {
+    start: Frequency,
+    end: Frequency,
+    duration: Time,
+    offset: Time,
+    amplitude: Amplitude,
+}
+
+impl LinearChirp {
+    pub fn new(start: Frequency, end: Frequency, duration: Time, amplitude: Amplitude) -> Self {
+        Self {
+            start,
+            end,
+            duration,
+            offset: Time::zero(),
+            amplitude,
+        }
+    }
+}
+
+impl Wave for LinearChirp {
+    fn shift_mut(&mut self, offset: Time) {
+        let new_offset = self.offset + offset;
+        let whole_sweeps = (new_offset / self.duration).floor();
+        self.offset = new_offset - (self.duration * whole_sweeps);
+    }
+
+    fn value_at(&self, t: Time) -> Amplitude {
+        let sweep_t = self.offset + t;
+        let sweep_t = sweep_t - self.duration * (sweep_t / self.duration).floor();
+        let rate = (self.end.value() - self.start.value()) / self.duration.value();
+        let cycles = sweep_t * self.start + rate * sweep_t.value().powi(2) / 2.0;
+        Amplitude::new((2.0f32 * std::f32::consts::PI * cycles).sin() * self.amplitude.value())
+    }
+}
+
+/// Estimates the instantaneous frequency of a sampled wave from the intervals between its zero
+/// crossings, yielding one estimate per half cycle
+///
+/// Crossing positions are linearly interpolated between samples. The input is expected to be
+/// free of DC bias.
+pub fn instantaneous_frequency(samples: Samples, rate: SamplingRate) -> Vec<Frequency> {
+    let crossings: Vec<f32> = samples
+        .as_slice()
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| (pair[0] < 0.0) != (pair[1] < 0.0))
+        .map(|(idx, pair)| idx as f32 + pair[0] / (pair[0] - pair[1]))
+        .collect();
+
+    crossings
+        .windows(2)
+        .map(|pair| Frequency::new((rate.value() as f32) / (2.0 * (pair[1] - pair[0]))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instantaneous_frequency_of_linear_chirp() {
+        let rate = 16000;
+        let (start, end) = (100.0f32, 1000.0f32);
+        let sweep = end - start;
+        let chirp: Vec<f32> = (0..rate)
+            .map(|i| {
+                let t = (i as f32) / (rate as f32);
+                (2.0 * std::f32::consts::PI * (start * t + sweep * t * t / 2.0)).sin()
+            })
+            .collect();
+
+        let estimates = instantaneous_frequency(Samples(&chirp), SamplingRate::new(rate));
+        assert!(estimates.len() > 100);
+
+        // The m-th zero crossing happens where the chirp's phase reaches m half cycles
+        let crossing = |m: f32| ((start * start + sweep * m).sqrt() - start) / sweep;
+        for (idx, estimate) in estimates.iter().enumerate() {
+            let midpoint = (crossing(idx as f32 + 1.0) + crossing(idx as f32 + 2.0)) / 2.0;
+            let expected = start + sweep * midpoint;
+            assert!((estimate.value() - expected).abs() < expected * 0.01);
+        }
+    }
+
+    #[test]
+    fn linear_chirp_sweeps_and_repeats() {
+        let rate = SamplingRate::new(16000);
+        let mut chirp = LinearChirp::new(
+            Frequency::new(100.0),
+            Frequency::new(1000.0),
+            Time::new(1.0),
+            Amplitude::new(1.0),
+        );
+        let sweep: Vec<f32> = (0..16000)
+            .map(|i| chirp.value_at(Time::new((i as f32) / 16000.0)).value())
+            .collect();
+        let estimates = instantaneous_frequency(Samples(&sweep), rate);
+        assert!((estimates[0].value() - 100.0).abs() < 10.0);
+        assert!((estimates.last().unwrap().value() - 1000.0).abs() < 10.0);
+
+        let start = chirp.value_at(Time::new(0.01));
+        chirp.shift_mut(Time::new(1.0));
+        assert!((chirp.value_at(Time::new(0.01)).value() - start.value()).abs() < 1e-3);
+    }
+
+    /// Energy outside the bins around the harmonics of `freq`, relative to the total
+    fn inharmonic_energy(samples: &[f32], freq: usize) -> f32 {
+        let bins = crate::proc::spectrum(Samples(samples), &mut rustfft::FftPlanner::new());
+        let energies: Vec<(usize, f32)> = bins
+            .iter()
+            .enumerate()
+            .take(samples.len() / 2)
+            .map(|(bin, x)| (bin, x.norm_sqr()))
+            .collect();
+        let total: f32 = energies.iter().map(|(_, e)| e).sum();
+        let inharmonic: f32 = energies
+            .iter()
+            .filter(|(bin, _)| {
+                let distance = bin % freq;
+                distance > 2 && distance < freq - 2
+            })
+            .map(|(_, e)| e)
+            .sum();
+        inharmonic / total
+    }
+
+    #[test]
+    fn band_limited_sawtooth_does_not_alias() {
+        let (rate, freq) = (8000usize, 1300usize);
+        let increment = 1.0 / (rate as f32);
+
+        let blit = BlitSawtooth::new(
+            Frequency::new(freq as f32),
+            Time::zero(),
+            Amplitude::new(1.0),
+            SamplingRate::new(rate),
+        );
+        let band_limited: Vec<f32> = (0..rate)
+            .map(|i| blit.value_at(Time::new(increment * (i as f32))).value())
+            .collect();
+        let naive: Vec<f32> = (0..rate)
+            .map(|i| {
+                let cycles = (freq as f32) * increment * (i as f32);
+                2.0 * (cycles - (cycles + 0.5).floor())
+            })
+            .collect();
+
+        assert!(inharmonic_energy(&band_limited, freq) < 1e-6);
+        assert!(inharmonic_energy(&naive, freq) > 1e-2);
+    }
+
+    #[test]
+    fn band_limited_square_levels() {
+        let square = BlitSquare::new(
+            Frequency::new(10.0),
+            Time::zero(),
+            Amplitude::new(1.0),
+            SamplingRate::new(48000),
+        );
+        assert!((square.value_at(Time::new(0.025)).value() - 1.0).abs() < 1e-2);
+        assert!((square.value_at(Time::new(0.075)).value() + 1.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn gaussian_pulse_shape() {
+        let pulse = GaussianPulse::new(Proportion::new(0.5), Time::new(1.0), Amplitude::new(2.0));
+        assert!((pulse.value_at(Time::new(0.5)).value() - 2.0).abs() < 1e-6);
+        assert!(pulse.value_at(Time::new(0.0)).value() < 0.4);
+        assert!((pulse.step_at(Time::new(0.5)).value() - 0.5).abs() < 1e-6);
+        assert!(pulse.step_at(Time::new(0.01)).value() < 0.05);
+        assert!(pulse.step_at(Time::new(0.99)).value() > 0.95);
     }
 }